@@ -0,0 +1,128 @@
+//! Compares the fast paths of the built-in validators with the
+//! scalar loops they replace.
+//!
+//! Run with `cargo bench --all-features`.
+
+#[macro_use]
+extern crate new_type_derive;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "normalization")]
+extern crate unicode_normalization;
+#[cfg(feature = "confusables")]
+extern crate unicode_security;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use new_type_derive::{Charset, CharsetError, LengthUnit};
+
+const ID_CHARS: Charset = Charset::ASCII_ALPHANUMERIC.with('-');
+
+fn validate_id(value: &str) -> Result<(), CharsetError> {
+    ID_CHARS.validate(value)
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-'
+}
+
+new_type_pair! {
+    #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct Id(String);
+
+    #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct IdRef(str) where validate = validate_id, error = CharsetError;
+}
+
+const VALUES: usize = 1_000_000;
+
+fn values(template: &str) -> Vec<String> {
+    (0..VALUES).map(|i| format!("{}-{:08}", template, i)).collect()
+}
+
+fn time<F: FnMut() -> usize>(name: &str, mut f: F) -> Duration {
+    black_box(f());
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    black_box(result);
+    println!("{:<40} {:>8.1} ns/value", name, elapsed.as_secs_f64() * 1e9 / VALUES as f64);
+    elapsed
+}
+
+fn compare<F: FnMut() -> usize, G: FnMut() -> usize>(name: &str, scalar: F, fast: G) {
+    let scalar = time(&format!("{} (scalar)", name), scalar);
+    let fast = time(&format!("{} (fast)", name), fast);
+    println!("{:<40} {:>8.2}x", "", scalar.as_secs_f64() / fast.as_secs_f64());
+}
+
+fn main() {
+    for &(label, template) in &[("ascii", "service-account"), ("latin", "ĝuste-ŝanĝita"), ("emoji", "👨‍👩‍👧-family")] {
+        let owned = values(template);
+        let values: Vec<&str> = owned.iter().map(String::as_str).collect();
+
+        compare(
+            &format!("chars, {}", label),
+            || values.iter().map(|value| value.chars().count()).sum(),
+            || values.iter().map(|value| LengthUnit::Chars.count(value)).sum(),
+        );
+        compare(
+            &format!("utf16, {}", label),
+            || values.iter().map(|value| value.chars().map(char::len_utf16).sum::<usize>()).sum(),
+            || values.iter().map(|value| LengthUnit::Utf16.count(value)).sum(),
+        );
+        compare(
+            &format!("charset, {}", label),
+            || values.iter().filter(|value| value.chars().all(is_id_char)).count(),
+            || values.iter().filter(|value| ID_CHARS.validate(value).is_ok()).count(),
+        );
+        compare(
+            &format!("try_as_ref_all, {}", label),
+            || {
+                let results: Vec<Result<&str, ()>> = values
+                    .iter()
+                    .map(|&value| if value.chars().all(is_id_char) { Ok(value) } else { Err(()) })
+                    .collect();
+                results.iter().filter(|result| result.is_ok()).count()
+            },
+            || IdRef::try_as_ref_all(&values).iter().filter(|result| result.is_ok()).count(),
+        );
+        normalization(label, &values);
+        confusables(label, &values);
+    }
+}
+
+#[cfg(feature = "normalization")]
+fn normalization(label: &str, values: &[&str]) {
+    compare(
+        &format!("validate_nfc, {}", label),
+        || values.iter().filter(|value| unicode_normalization::is_nfc(value)).count(),
+        || values.iter().filter(|value| new_type_derive::validate_nfc(value).is_ok()).count(),
+    );
+}
+
+#[cfg(not(feature = "normalization"))]
+fn normalization(_: &str, _: &[&str]) {}
+
+#[cfg(feature = "confusables")]
+fn confusables(label: &str, values: &[&str]) {
+    use unicode_security::{GeneralSecurityProfile, RestrictionLevel, RestrictionLevelDetection};
+
+    compare(
+        &format!("validate_confusables, {}", label),
+        || {
+            values
+                .iter()
+                .filter(|value| {
+                    value.chars().all(|c| c.identifier_allowed())
+                        && value.check_restriction_level(RestrictionLevel::HighlyRestrictive)
+                })
+                .count()
+        },
+        || values.iter().filter(|value| new_type_derive::validate_confusables(value).is_ok()).count(),
+    );
+}
+
+#[cfg(not(feature = "confusables"))]
+fn confusables(_: &str, _: &[&str]) {}
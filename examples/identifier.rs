@@ -47,6 +47,10 @@ impl ShortIdRef {
     pub fn len(&self) -> usize {
         self.inner.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -63,7 +67,7 @@ pub fn main() {
     use std::hash::{Hash, Hasher};
 
     let id = {
-        let mut args_iter = env::args().into_iter();
+        let mut args_iter = env::args();
         args_iter.next();
         args_iter.next().unwrap_or_else(|| String::from("generic"))
     };
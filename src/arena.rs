@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::cmp;
+
+use traits::TransparentRef;
+
+const DEFAULT_CHUNK_CAPACITY: usize = 4096;
+
+/// A bump arena that stores validated values and hands out typed references
+/// to them, without an allocation per value.
+///
+/// Values are copied into large shared buffers that live until the arena is
+/// dropped, so references returned from the arena borrow the arena itself.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::NewTypeArena;
+///
+/// fn validate_word(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() || !value.chars().all(char::is_alphabetic) {
+///         return Err("not a word");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Word(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct WordRef(str) where validate = validate_word, error = &'static str;
+/// }
+///
+/// # pub fn main() {
+/// let arena = NewTypeArena::new();
+/// let words: Vec<&WordRef> = "the quick brown fox"
+///     .split(' ')
+///     .map(|w| arena.alloc_validated::<WordRef>(w))
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!("quick", words[1]);
+/// assert!(arena.alloc_validated::<WordRef>("two words").is_err());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct NewTypeArena {
+    chunks: RefCell<Chunks>,
+}
+
+#[derive(Debug)]
+struct Chunks {
+    current: String,
+    full: Vec<String>,
+}
+
+impl NewTypeArena {
+    /// Creates an empty arena
+    pub fn new() -> Self {
+        NewTypeArena::with_capacity(DEFAULT_CHUNK_CAPACITY)
+    }
+
+    /// Creates an empty arena whose first buffer holds `capacity` bytes
+    pub fn with_capacity(capacity: usize) -> Self {
+        NewTypeArena {
+            chunks: RefCell::new(Chunks {
+                current: String::with_capacity(capacity),
+                full: Vec::new(),
+            }),
+        }
+    }
+
+    /// Validates `value` and then copies it into the arena, returning a typed
+    /// reference to the copy or an error
+    pub fn alloc_validated<R>(&self, value: &str) -> Result<&R, R::ValidationError>
+    where R: ?Sized + TransparentRef<InnerRef = str> {
+        R::validate(value)?;
+        let value = self.alloc_str(value);
+        Ok(#[allow(unsafe_code)] unsafe { R::from_inner_unchecked(value) })
+    }
+
+    /// Copies an already validated reference into the arena
+    pub fn alloc_ref<R>(&self, value: &R) -> &R
+    where R: ?Sized + TransparentRef<InnerRef = str> + AsRef<str> {
+        let value = self.alloc_str(value.as_ref());
+        #[allow(unsafe_code)]
+        unsafe { R::from_inner_unchecked(value) }
+    }
+
+    /// The number of bytes of values stored in the arena
+    pub fn allocated_bytes(&self) -> usize {
+        let chunks = self.chunks.borrow();
+        chunks.current.len() + chunks.full.iter().map(String::len).sum::<usize>()
+    }
+
+    fn alloc_str(&self, value: &str) -> &str {
+        let mut chunks = self.chunks.borrow_mut();
+        if chunks.current.capacity() - chunks.current.len() < value.len() {
+            let capacity = cmp::max(chunks.current.capacity() * 2, value.len());
+            let full = ::std::mem::replace(&mut chunks.current, String::with_capacity(capacity));
+            chunks.full.push(full);
+        }
+        let start = chunks.current.len();
+        chunks.current.push_str(value);
+        let stored: *const str = &chunks.current[start..];
+        // The chunk had room for the value, so pushing it did not move the
+        // buffer, and buffers are only dropped with the arena.
+        #[allow(unsafe_code)]
+        unsafe { &*stored }
+    }
+}
+
+impl Default for NewTypeArena {
+    fn default() -> Self {
+        NewTypeArena::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NewTypeArena;
+
+    fn validate_word(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || !value.chars().all(char::is_alphabetic) {
+            return Err("not a word");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Word(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct WordRef(str) where validate = validate_word, error = &'static str;
+    }
+
+    #[test]
+    fn validates_before_allocating() {
+        let arena = NewTypeArena::new();
+        assert_eq!(Err("not a word"), arena.alloc_validated::<WordRef>("x1").map(|_| ()));
+        assert_eq!(0, arena.allocated_bytes());
+        let word = arena.alloc_validated::<WordRef>("ĝuste").unwrap();
+        assert_eq!("ĝuste", word);
+        assert_eq!("ĝuste".len(), arena.allocated_bytes());
+    }
+
+    #[test]
+    fn references_survive_growth() {
+        let arena = NewTypeArena::with_capacity(8);
+        let first = arena.alloc_validated::<WordRef>("first").unwrap();
+        let words: Vec<&WordRef> = (0..200)
+            .map(|i| {
+                let value = "a".repeat(i % 17 + 1);
+                arena.alloc_validated::<WordRef>(&value).unwrap()
+            })
+            .collect();
+        let long = "z".repeat(1000);
+        let last = arena.alloc_validated::<WordRef>(&long).unwrap();
+        assert_eq!("first", first);
+        for (i, word) in words.iter().enumerate() {
+            assert_eq!(i % 17 + 1, AsRef::<str>::as_ref(*word).len());
+        }
+        assert_eq!(long, AsRef::<str>::as_ref(last));
+    }
+
+    #[test]
+    fn copies_validated_refs() {
+        let arena = NewTypeArena::new();
+        let source = String::from("copied");
+        let copied = arena.alloc_ref(WordRef::try_as_ref(&source).unwrap());
+        drop(source);
+        assert_eq!("copied", copied);
+    }
+}
@@ -0,0 +1,291 @@
+use std::error::Error;
+use std::fmt;
+
+use stream::{StreamError, Utf8Chunks};
+use traits::ValidatorState;
+
+const MAX_RANGES: usize = 8;
+
+/// A set of ASCII characters that values may be made of, for use from
+/// `NewTypeRef::validate`.
+///
+/// Sets are built from up to eight ranges of characters in `const` contexts.
+/// On x86-64, values are checked sixteen bytes at a time with SSE2
+/// instructions, and one byte at a time elsewhere.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::{Charset, CharsetError};
+///
+/// const SLUG_CHARS: Charset = Charset::new().range('a', 'z').range('0', '9').with('-');
+///
+/// fn validate_slug(value: &str) -> Result<(), CharsetError> {
+///     SLUG_CHARS.validate(value)
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Slug(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct SlugRef(str) where validate = validate_slug, error = CharsetError;
+/// }
+///
+/// # pub fn main() {
+/// assert!(Slug::try_from("release-notes-2").is_ok());
+/// let error = Slug::try_from("release notes").unwrap_err();
+/// assert_eq!(' ', error.character());
+/// assert_eq!(7, error.index());
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Charset {
+    ranges: [(u8, u8); MAX_RANGES],
+    len: usize,
+}
+
+impl Charset {
+    /// ASCII digits, `0` to `9`
+    pub const ASCII_DIGITS: Charset = Charset::new().range('0', '9');
+
+    /// ASCII letters and digits
+    pub const ASCII_ALPHANUMERIC: Charset = Charset::new().range('0', '9').range('A', 'Z').range('a', 'z');
+
+    /// ASCII characters other than control characters
+    pub const ASCII_PRINTABLE: Charset = Charset::new().range(' ', '~');
+
+    /// An empty set, to which characters are added with `range` and `with`
+    pub const fn new() -> Self {
+        Charset { ranges: [(0, 0); MAX_RANGES], len: 0 }
+    }
+
+    /// Adds the characters from `first` to `last`, inclusive
+    ///
+    /// # Panics
+    ///
+    /// Panics if either character is not ASCII, if `first` comes after
+    /// `last`, or if the set already has eight ranges.
+    pub const fn range(self, first: char, last: char) -> Self {
+        assert!(first.is_ascii() && last.is_ascii(), "charsets can only hold ASCII characters");
+        assert!(first as u32 <= last as u32, "the range is empty");
+        assert!(self.len < MAX_RANGES, "charsets hold at most eight ranges");
+        let mut ranges = self.ranges;
+        ranges[self.len] = (first as u8, last as u8);
+        Charset { ranges, len: self.len + 1 }
+    }
+
+    /// Adds a single character
+    ///
+    /// # Panics
+    ///
+    /// Panics if the character is not ASCII, or if the set already has eight
+    /// ranges.
+    pub const fn with(self, c: char) -> Self {
+        self.range(c, c)
+    }
+
+    /// Whether the set holds `c`
+    pub fn contains(&self, c: char) -> bool {
+        c.is_ascii() && self.contains_byte(c as u8)
+    }
+
+    /// Checks that every character of `value` is in the set
+    pub fn validate(&self, value: &str) -> Result<(), CharsetError> {
+        match self.position(value.as_bytes()) {
+            None => Ok(()),
+            Some(index) => Err(CharsetError {
+                character: value[index..].chars().next().unwrap_or_default(),
+                index,
+            }),
+        }
+    }
+
+    /// Creates a `ValidatorState` that checks a value against the set one
+    /// chunk at a time
+    pub fn stream(&self) -> CharsetState {
+        CharsetState { charset: *self, offset: 0, chunks: Utf8Chunks::default() }
+    }
+
+    fn ranges(&self) -> &[(u8, u8)] {
+        &self.ranges[..self.len]
+    }
+
+    fn contains_byte(&self, byte: u8) -> bool {
+        self.ranges().iter().any(|&(first, last)| first <= byte && byte <= last)
+    }
+
+    // Finds the first byte outside the set. Every range is ASCII, so this is
+    // never a continuation byte, and `index` is a character boundary.
+    fn position(&self, bytes: &[u8]) -> Option<usize> {
+        let checked = match first_outside(self.ranges(), bytes) {
+            Ok(checked) => checked,
+            Err(index) => return Some(index),
+        };
+        bytes[checked..]
+            .iter()
+            .position(|&byte| !self.contains_byte(byte))
+            .map(|index| checked + index)
+    }
+}
+
+// Checks whole blocks of sixteen bytes, returning how many bytes were found
+// to be in the set, or the index of the first one that is not. SSE2 is part
+// of the x86-64 baseline, so it needs no detection at runtime. A byte is in
+// the range from `first` to `last` when subtracting `first` from it, with
+// wrapping, leaves at most `last - first`, which saturating subtraction of
+// the latter turns into a comparison with zero.
+#[cfg(target_arch = "x86_64")]
+#[allow(unsafe_code)]
+fn first_outside(ranges: &[(u8, u8)], bytes: &[u8]) -> Result<usize, usize> {
+    use std::arch::x86_64::{
+        __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_or_si128, _mm_set1_epi8,
+        _mm_setzero_si128, _mm_sub_epi8, _mm_subs_epu8,
+    };
+
+    let mut checked = 0;
+    while checked + 16 <= bytes.len() {
+        let mask = unsafe {
+            let block = _mm_loadu_si128(bytes.as_ptr().add(checked) as *const __m128i);
+            let zero = _mm_setzero_si128();
+            let mut inside = zero;
+            for &(first, last) in ranges {
+                let offset = _mm_sub_epi8(block, _mm_set1_epi8(first as i8));
+                let beyond = _mm_subs_epu8(offset, _mm_set1_epi8((last - first) as i8));
+                inside = _mm_or_si128(inside, _mm_cmpeq_epi8(beyond, zero));
+            }
+            _mm_movemask_epi8(inside) as u32
+        };
+        if mask != 0xffff {
+            return Err(checked + (!mask).trailing_zeros() as usize);
+        }
+        checked += 16;
+    }
+    Ok(checked)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn first_outside(_: &[(u8, u8)], _: &[u8]) -> Result<usize, usize> {
+    Ok(0)
+}
+
+/// Checks a value against a `Charset` one chunk at a time, as created by
+/// `Charset::stream`.
+///
+/// ```
+/// use new_type_derive::{Charset, ValidatorState};
+///
+/// let mut state = Charset::ASCII_DIGITS.stream();
+/// state.feed(b"0451").unwrap();
+/// assert!(state.feed(b"x").is_err());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CharsetState {
+    charset: Charset,
+    offset: usize,
+    chunks: Utf8Chunks,
+}
+
+impl ValidatorState for CharsetState {
+    type Error = StreamError<CharsetError>;
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        let mut chunks = self.chunks;
+        let result = chunks.feed(chunk, |piece| {
+            let offset = self.offset;
+            self.offset += piece.len();
+            self.charset.validate(piece).map_err(|error| CharsetError { index: offset + error.index, ..error })
+        });
+        self.chunks = chunks;
+        result
+    }
+
+    fn finish(self) -> Result<(), Self::Error> {
+        self.chunks.finish()
+    }
+}
+
+/// The error returned when a value holds a character outside a `Charset`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CharsetError {
+    character: char,
+    index: usize,
+}
+
+impl CharsetError {
+    /// The first character that is not in the set
+    pub fn character(&self) -> char {
+        self.character
+    }
+
+    /// The byte index of the character in the value
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl fmt::Display for CharsetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "character {:?} at byte {} is not allowed", self.character, self.index)
+    }
+}
+
+impl Error for CharsetError {}
+
+#[cfg(test)]
+mod test {
+    use super::Charset;
+    use stream::StreamError;
+    use traits::ValidatorState;
+
+    const ID_CHARS: Charset = Charset::ASCII_ALPHANUMERIC.with('-').with('_');
+
+    #[test]
+    fn holds_its_ranges() {
+        assert!(ID_CHARS.contains('a') && ID_CHARS.contains('Z') && ID_CHARS.contains('_'));
+        assert!(!ID_CHARS.contains(' ') && !ID_CHARS.contains('ĝ') && !ID_CHARS.contains('\0'));
+        assert!(!Charset::new().contains('a'));
+        assert!(Charset::ASCII_PRINTABLE.contains('~') && !Charset::ASCII_PRINTABLE.contains('\x7f'));
+    }
+
+    #[test]
+    fn reports_the_first_character_outside() {
+        assert_eq!(Ok(()), ID_CHARS.validate(""));
+        assert_eq!(Ok(()), ID_CHARS.validate("service-account_0123456789"));
+        let error = ID_CHARS.validate("service-account-ĝuste").unwrap_err();
+        assert_eq!(('ĝ', 16), (error.character(), error.index()));
+        assert_eq!("character 'ĝ' at byte 16 is not allowed", error.to_string());
+        let error = Charset::ASCII_DIGITS.validate("0123456789012345678 9").unwrap_err();
+        assert_eq!((' ', 19), (error.character(), error.index()));
+    }
+
+    #[test]
+    #[should_panic(expected = "charsets can only hold ASCII characters")]
+    fn rejects_characters_outside_ascii() {
+        Charset::new().with('ĝ');
+    }
+
+    fn stream(value: &str, size: usize) -> Result<(), StreamError<super::CharsetError>> {
+        let mut state = ID_CHARS.stream();
+        for chunk in value.as_bytes().chunks(size) {
+            state.feed(chunk)?;
+        }
+        state.finish()
+    }
+
+    proptest! {
+        #[test]
+        fn agrees_with_a_scalar_loop(ref s in "[a-z0-9_ĝ -]{0,40}") {
+            let expected = s.char_indices().find(|&(_, c)| !ID_CHARS.contains(c));
+            let actual = ID_CHARS.validate(s).err().map(|error| (error.index(), error.character()));
+            assert_eq!(expected, actual);
+        }
+
+        #[test]
+        fn streams_agree_with_validate(ref s in "[a-z0-9_ĝ -]{0,40}", size in 1usize..20) {
+            assert_eq!(ID_CHARS.validate(s).map_err(StreamError::Invalid), stream(s, size));
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use traits::Comparison;
+
+/// Compares values ignoring the case of ASCII letters, leaving all other
+/// characters as they are. Suited to hostnames and HTTP header names.
+///
+/// The canonical form of a value is its ASCII lowercase form.
+#[derive(Clone, Copy, Debug)]
+pub enum AsciiCaseInsensitive {}
+
+impl Comparison for AsciiCaseInsensitive {
+    #[inline]
+    fn eq(lhs: &str, rhs: &str) -> bool {
+        lhs.eq_ignore_ascii_case(rhs)
+    }
+
+    fn cmp(lhs: &str, rhs: &str) -> Ordering {
+        let lhs = lhs.bytes().map(|b| b.to_ascii_lowercase());
+        let rhs = rhs.bytes().map(|b| b.to_ascii_lowercase());
+        lhs.cmp(rhs)
+    }
+
+    fn hash<H: Hasher>(value: &str, state: &mut H) {
+        for b in value.bytes() {
+            state.write_u8(b.to_ascii_lowercase());
+        }
+        state.write_u8(0xff);
+    }
+
+    fn canonical(value: &str) -> Cow<'_, str> {
+        if value.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(value.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(value)
+        }
+    }
+}
+
+/// Compares values after folding the case of every character, so that,
+/// for example, `"STRASSE"` and `"straße"` are equal.
+///
+/// Characters are folded by uppercasing and then lowercasing them, which
+/// agrees with Unicode default case folding for all but a few characters.
+/// The canonical form of a value is its folded form.
+#[derive(Clone, Copy, Debug)]
+pub enum CaseInsensitive {}
+
+fn fold<'a>(value: &'a str) -> impl Iterator<Item = char> + 'a {
+    value
+        .chars()
+        .flat_map(char::to_uppercase)
+        .flat_map(char::to_lowercase)
+}
+
+impl Comparison for CaseInsensitive {
+    #[inline]
+    fn eq(lhs: &str, rhs: &str) -> bool {
+        fold(lhs).eq(fold(rhs))
+    }
+
+    #[inline]
+    fn cmp(lhs: &str, rhs: &str) -> Ordering {
+        fold(lhs).cmp(fold(rhs))
+    }
+
+    fn hash<H: Hasher>(value: &str, state: &mut H) {
+        for c in fold(value) {
+            state.write_u32(c as u32);
+        }
+        state.write_u8(0xff);
+    }
+
+    fn canonical(value: &str) -> Cow<'_, str> {
+        if fold(value).eq(value.chars()) {
+            Cow::Borrowed(value)
+        } else {
+            Cow::Owned(fold(value).collect())
+        }
+    }
+}
+
+/// Orders values naturally, comparing runs of ASCII digits by their numeric
+/// value so that `"item9"` sorts before `"item10"`. Values are equal only
+/// when they are identical, so values that only differ in leading zeros,
+/// such as `"v01"` and `"v1"`, are ordered byte-wise.
+///
+/// The canonical form of a value is the value itself.
+#[derive(Clone, Copy, Debug)]
+pub enum NaturalOrder {}
+
+fn split_digits(value: &[u8]) -> (&[u8], &[u8]) {
+    let end = value.iter().position(|b| !b.is_ascii_digit()).unwrap_or(value.len());
+    value.split_at(end)
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let start = digits.iter().position(|&b| b != b'0').unwrap_or(digits.len());
+    &digits[start..]
+}
+
+fn natural_cmp(mut lhs: &[u8], mut rhs: &[u8]) -> Ordering {
+    loop {
+        match (lhs.first(), rhs.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) if l.is_ascii_digit() && r.is_ascii_digit() => {
+                let (l, lrest) = split_digits(lhs);
+                let (r, rrest) = split_digits(rhs);
+                let (l, r) = (trim_zeros(l), trim_zeros(r));
+                let order = l.len().cmp(&r.len()).then_with(|| l.cmp(r));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                lhs = lrest;
+                rhs = rrest;
+            }
+            (Some(l), Some(r)) => {
+                if l != r {
+                    return l.cmp(r);
+                }
+                lhs = &lhs[1..];
+                rhs = &rhs[1..];
+            }
+        }
+    }
+}
+
+impl Comparison for NaturalOrder {
+    #[inline]
+    fn eq(lhs: &str, rhs: &str) -> bool {
+        lhs == rhs
+    }
+
+    fn cmp(lhs: &str, rhs: &str) -> Ordering {
+        // Ties between runs of digits that differ only in leading zeros are
+        // broken byte-wise, so that only identical values compare equal.
+        natural_cmp(lhs.as_bytes(), rhs.as_bytes()).then_with(|| lhs.cmp(rhs))
+    }
+
+    #[inline]
+    fn hash<H: Hasher>(value: &str, state: &mut H) {
+        Hash::hash(value, state)
+    }
+
+    #[inline]
+    fn canonical(value: &str) -> Cow<'_, str> {
+        Cow::Borrowed(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AsciiCaseInsensitive, CaseInsensitive, NaturalOrder};
+    use std::borrow::Cow;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+    use traits::Comparison;
+
+    fn hash_of<C: Comparison>(value: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        C::hash(value, &mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn ascii_ignores_only_ascii_case() {
+        assert!(AsciiCaseInsensitive::eq("Example.COM", "example.com"));
+        assert!(!AsciiCaseInsensitive::eq("ÉCOLE", "école"));
+        assert_eq!(Ordering::Less, AsciiCaseInsensitive::cmp("Apple", "banana"));
+        assert_eq!(
+            hash_of::<AsciiCaseInsensitive>("Content-Type"),
+            hash_of::<AsciiCaseInsensitive>("content-type")
+        );
+        assert_eq!(Cow::Borrowed("lower"), AsciiCaseInsensitive::canonical("lower"));
+        assert_eq!("mixed", AsciiCaseInsensitive::canonical("MiXeD"));
+    }
+
+    #[test]
+    fn unicode_folds_every_character() {
+        assert!(CaseInsensitive::eq("ÉCOLE", "école"));
+        assert!(CaseInsensitive::eq("STRASSE", "straße"));
+        assert!(CaseInsensitive::eq("ΣΊΣΥΦΟΣ", "σίσυφος"));
+        assert_eq!(Ordering::Equal, CaseInsensitive::cmp("STRASSE", "straße"));
+        assert_eq!(Ordering::Less, CaseInsensitive::cmp("Ä", "ö"));
+        assert_eq!(
+            hash_of::<CaseInsensitive>("STRASSE"),
+            hash_of::<CaseInsensitive>("straße")
+        );
+        assert_eq!("strasse", CaseInsensitive::canonical("Straße"));
+    }
+
+    #[test]
+    fn orders_numbers_naturally() {
+        let mut items = vec!["item10", "item9", "item1", "item01", "item", "item9a", "b2", "a10b"];
+        items.sort_by(|l, r| NaturalOrder::cmp(l, r));
+        assert_eq!(vec!["a10b", "b2", "item", "item01", "item1", "item9", "item9a", "item10"], items);
+        assert_eq!(Ordering::Less, NaturalOrder::cmp("1.9.0", "1.10.0"));
+        assert_eq!(Ordering::Equal, NaturalOrder::cmp("v007", "v007"));
+        assert!(!NaturalOrder::eq("v007", "v7"));
+    }
+
+    proptest! {
+        #[test]
+        fn natural_order_is_consistent(ref a in "[a0-2]{0,5}", ref b in "[a0-2]{0,5}", ref c in "[a0-2]{0,5}") {
+            assert_eq!(NaturalOrder::eq(a, b), NaturalOrder::cmp(a, b) == Ordering::Equal);
+            assert_eq!(NaturalOrder::cmp(a, b), NaturalOrder::cmp(b, a).reverse());
+            if NaturalOrder::cmp(a, b) != Ordering::Greater && NaturalOrder::cmp(b, c) != Ordering::Greater {
+                assert_ne!(Ordering::Greater, NaturalOrder::cmp(a, c));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn folding_is_consistent(ref a in ".{0,8}", ref b in ".{0,8}") {
+            let upper = a.to_uppercase();
+            assert!(CaseInsensitive::eq(a, &upper));
+            assert!(AsciiCaseInsensitive::eq(&a.to_ascii_uppercase(), a));
+            assert_eq!(CaseInsensitive::eq(a, b), CaseInsensitive::cmp(a, b) == Ordering::Equal);
+            assert_eq!(
+                CaseInsensitive::eq(a, b),
+                CaseInsensitive::canonical(a) == CaseInsensitive::canonical(b)
+            );
+            assert_eq!(
+                AsciiCaseInsensitive::eq(a, b),
+                AsciiCaseInsensitive::cmp(a, b) == Ordering::Equal
+            );
+        }
+    }
+}
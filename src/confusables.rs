@@ -0,0 +1,215 @@
+use std::error::Error;
+use std::fmt;
+use unicode_security::{self, GeneralSecurityProfile, RestrictionLevel, RestrictionLevelDetection};
+use unicode_security::mixed_script::AugmentedScriptSet;
+
+use stream::{StreamError, Utf8Chunks};
+use traits::{NewTypeRef, ValidatorState};
+
+/// The error returned by `validate_confusables` for values that could be
+/// used to spoof other values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConfusableError {
+    /// The value contains a character that the Unicode General Security
+    /// Profile does not allow in identifiers
+    Disallowed(char),
+    /// The value mixes scripts in a way that is not highly restrictive, such
+    /// as Latin letters along with Cyrillic ones
+    MixedScript,
+}
+
+impl fmt::Display for ConfusableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfusableError::Disallowed(c) => write!(f, "character {:?} is not allowed in identifiers", c),
+            ConfusableError::MixedScript => f.write_str("value mixes scripts that could be confused"),
+        }
+    }
+}
+
+impl Error for ConfusableError {}
+
+/// Requires `value` to contain only characters allowed in identifiers by
+/// the Unicode General Security Profile, and to be highly restrictive in
+/// the sense of [UTS #39], so that it cannot mix, for example, Latin and
+/// Cyrillic letters.
+///
+/// Values that pass may still be confusable with other single-script
+/// values, which comparing their `skeleton`s detects.
+///
+/// [UTS #39]: https://www.unicode.org/reports/tr39/#Restriction_Level_Detection
+pub fn validate_confusables(value: &str) -> Result<(), ConfusableError> {
+    if let Some(c) = value.chars().find(|c| !c.identifier_allowed()) {
+        return Err(ConfusableError::Disallowed(c));
+    }
+    // ASCII values only use the Latin and Common scripts.
+    if value.is_ascii() {
+        return Ok(());
+    }
+    if !value.check_restriction_level(RestrictionLevel::HighlyRestrictive) {
+        return Err(ConfusableError::MixedScript);
+    }
+    Ok(())
+}
+
+/// Checks a value one chunk at a time, as `validate_confusables` would.
+///
+/// Disallowed characters are rejected as soon as they are fed, while mixed
+/// scripts are only reported by `finish`.
+///
+/// ```
+/// use new_type_derive::{ConfusableError, ConfusablesState, StreamError, ValidatorState};
+///
+/// let mut state = ConfusablesState::default();
+/// state.feed("\u{442}".as_bytes()).unwrap();
+/// state.feed(b"est").unwrap();
+/// assert_eq!(Err(StreamError::Invalid(ConfusableError::MixedScript)), state.finish());
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConfusablesState {
+    non_ascii: bool,
+    // The scripts shared by every character, and by every character that
+    // is not Latin, as `RestrictionLevelDetection` computes them.
+    scripts: AugmentedScriptSet,
+    non_latin_scripts: AugmentedScriptSet,
+    chunks: Utf8Chunks,
+}
+
+impl ConfusablesState {
+    fn push(&mut self, piece: &str) -> Result<(), ConfusableError> {
+        let latin = AugmentedScriptSet::for_char('a').base;
+        for c in piece.chars() {
+            if !c.identifier_allowed() {
+                return Err(ConfusableError::Disallowed(c));
+            }
+            self.non_ascii |= !c.is_ascii();
+            let scripts = AugmentedScriptSet::for_char(c);
+            self.scripts.intersect_with(scripts);
+            if scripts.base.intersection(latin).is_empty() {
+                self.non_latin_scripts.intersect_with(scripts);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ValidatorState for ConfusablesState {
+    type Error = StreamError<ConfusableError>;
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        let mut chunks = self.chunks;
+        let result = chunks.feed(chunk, |piece| self.push(piece));
+        self.chunks = chunks;
+        result
+    }
+
+    fn finish(self) -> Result<(), Self::Error> {
+        self.chunks.finish()?;
+        let scripts = self.non_latin_scripts;
+        if self.non_ascii && self.scripts.is_empty() && !(scripts.kore || scripts.hanb || scripts.jpan) {
+            return Err(StreamError::Invalid(ConfusableError::MixedScript));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the confusable skeleton of `value`, which is the same for values
+/// that look alike, such as `"paypal"` and `"раураl"` spelled with Cyrillic
+/// letters.
+pub fn skeleton(value: &str) -> String {
+    unicode_security::skeleton(value).collect()
+}
+
+/// Adds a `skeleton` method to every reference type wrapping `str`, which
+/// can serve as the key of a uniqueness index.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::{validate_confusables, ConfusableError, Skeleton};
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Slug(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct SlugRef(str) where validate = validate_confusables, error = ConfusableError;
+/// }
+///
+/// # pub fn main() {
+/// assert_eq!(Err(ConfusableError::MixedScript), Slug::try_from("p\u{430}ypal"));
+/// let latin = Slug::try_from("paypal").unwrap();
+/// let cyrillic = Slug::try_from("\u{440}\u{430}\u{443}\u{440}\u{430}\u{406}").unwrap();
+/// assert_eq!(latin.skeleton(), cyrillic.skeleton());
+/// # }
+/// ```
+pub trait Skeleton {
+    /// Returns the confusable skeleton of the value
+    fn skeleton(&self) -> String;
+}
+
+impl<R: ?Sized + NewTypeRef<InnerRef = str> + AsRef<str>> Skeleton for R {
+    fn skeleton(&self) -> String {
+        skeleton(self.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{skeleton, validate_confusables, ConfusableError, ConfusablesState, Skeleton};
+    use stream::StreamError;
+    use traits::ValidatorState;
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Username(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct UsernameRef(str) where validate = validate_confusables, error = ConfusableError;
+    }
+
+    #[test]
+    fn rejects_mixed_scripts() {
+        assert_eq!(Ok(()), validate_confusables("ĝuste"));
+        assert_eq!(Ok(()), validate_confusables("\u{442}\u{435}\u{441}\u{442}"));
+        assert_eq!(Ok(()), validate_confusables("東京tokyo"));
+        assert_eq!(Err(ConfusableError::MixedScript), validate_confusables("\u{442}est"));
+        assert_eq!(Err(ConfusableError::Disallowed('\u{200b}')), validate_confusables("a\u{200b}b"));
+    }
+
+    #[test]
+    fn skeletons_match_lookalikes() {
+        assert_eq!(skeleton("rn"), skeleton("m"));
+        let latin = UsernameRef::try_as_ref("scope").unwrap();
+        let cyrillic = UsernameRef::try_as_ref("\u{455}\u{441}\u{43e}\u{440}\u{435}").unwrap();
+        assert_ne!(AsRef::<str>::as_ref(latin), AsRef::<str>::as_ref(cyrillic));
+        assert_eq!(latin.skeleton(), cyrillic.skeleton());
+        assert_eq!(latin.skeleton(), Username::try_from("scope").unwrap().skeleton());
+    }
+
+    fn stream(value: &str, size: usize) -> Result<(), StreamError<ConfusableError>> {
+        let mut state = ConfusablesState::default();
+        for chunk in value.as_bytes().chunks(size) {
+            state.feed(chunk)?;
+        }
+        state.finish()
+    }
+
+    #[test]
+    fn streams_reject_disallowed_characters_early() {
+        let mut state = ConfusablesState::default();
+        assert_eq!(Ok(()), state.feed(b"a\xe2\x80"));
+        assert_eq!(Err(StreamError::Invalid(ConfusableError::Disallowed('\u{200b}'))), state.feed(b"\x8b"));
+        assert_eq!(Ok(()), stream("\u{6771}\u{4eac}tokyo", 1));
+        assert_eq!(Err(StreamError::Invalid(ConfusableError::MixedScript)), stream("\u{442}est", 2));
+    }
+
+    proptest! {
+        #[test]
+        fn streams_agree_with_validate(ref s in "[a-z0-9\u{430}-\u{44f}\u{3b1}-\u{3c9}\u{3041}-\u{3093}\u{4e00}-\u{4e20}\u{ac00}-\u{ac20}\u{200b}]{0,8}", size in 1usize..6) {
+            assert_eq!(validate_confusables(s).map_err(StreamError::Invalid), stream(s, size));
+        }
+    }
+}
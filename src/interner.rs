@@ -0,0 +1,504 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "serde")]
+use serde;
+
+use traits::TransparentRef;
+
+/// A small, copyable handle to a value interned in a `SymbolTable`.
+///
+/// Symbols compare and hash by their table and their position in it, so
+/// comparing two symbols takes constant time. Symbols from the same table
+/// are ordered by when their values were first interned, not by the values
+/// themselves.
+pub struct Symbol<R: ?Sized> {
+    table: u32,
+    index: u32,
+    marker: PhantomData<fn(&R)>,
+}
+
+impl<R: ?Sized> Symbol<R> {
+    /// The position of the symbol in its table, suitable for indexing
+    /// side tables
+    #[inline]
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+impl<R: ?Sized> Clone for Symbol<R> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R: ?Sized> Copy for Symbol<R> {}
+
+impl<R: ?Sized> PartialEq for Symbol<R> {
+    #[inline]
+    fn eq(&self, rhs: &Self) -> bool {
+        (self.table, self.index) == (rhs.table, rhs.index)
+    }
+}
+
+impl<R: ?Sized> Eq for Symbol<R> {}
+
+impl<R: ?Sized> PartialOrd for Symbol<R> {
+    #[inline]
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+impl<R: ?Sized> Ord for Symbol<R> {
+    #[inline]
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        (self.table, self.index).cmp(&(rhs.table, rhs.index))
+    }
+}
+
+impl<R: ?Sized> Hash for Symbol<R> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.table, self.index).hash(state)
+    }
+}
+
+impl<R: ?Sized> fmt::Debug for Symbol<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Symbol({})", self.index)
+    }
+}
+
+/// A table of validated values that hands out a `Symbol` for each distinct
+/// value.
+///
+/// Values can only enter the table through `NewTypeRef::validate`, so every
+/// symbol resolves to a valid reference.
+pub trait SymbolTable<R: ?Sized + TransparentRef<InnerRef = str> + AsRef<str>> {
+    /// Validates `value` and returns its symbol, adding it to the table if it
+    /// has not been interned before
+    fn intern(&self, value: &str) -> Result<Symbol<R>, R::ValidationError>;
+
+    /// Returns the symbol of an already validated reference, adding it to the
+    /// table if it has not been interned before
+    fn intern_ref(&self, value: &R) -> Symbol<R>;
+
+    /// Returns the symbol for `value` if it has already been interned
+    fn get(&self, value: &str) -> Option<Symbol<R>>;
+
+    /// Returns the value that `symbol` stands for
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` was not handed out by this table.
+    fn resolve(&self, symbol: Symbol<R>) -> &R;
+
+    /// The number of distinct values in the table
+    fn len(&self) -> usize;
+
+    /// Whether the table holds no values
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Tells tables apart, so that symbols are only resolved by the table that
+// handed them out. Ids are reused only after four billion tables.
+static NEXT_TABLE_ID: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug)]
+struct Table {
+    id: u32,
+    values: Vec<Arc<str>>,
+    lookup: HashMap<Arc<str>, u32>,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Table {
+            id: NEXT_TABLE_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            values: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+}
+
+impl Table {
+    fn symbol<R: ?Sized>(&self, index: u32) -> Symbol<R> {
+        Symbol { table: self.id, index, marker: PhantomData }
+    }
+
+    fn get(&self, value: &str) -> Option<u32> {
+        self.lookup.get(value).cloned()
+    }
+
+    fn insert(&mut self, value: &str) -> u32 {
+        if let Some(index) = self.get(value) {
+            return index;
+        }
+        assert!(self.values.len() < u32::MAX as usize, "symbol table is full");
+        let index = self.values.len() as u32;
+        let value: Arc<str> = Arc::from(value);
+        self.values.push(Arc::clone(&value));
+        self.lookup.insert(value, index);
+        index
+    }
+
+    fn value<R: ?Sized>(&self, symbol: Symbol<R>) -> *const str {
+        assert_eq!(self.id, symbol.table, "symbol was not handed out by this table");
+        &*self.values[symbol.index as usize]
+    }
+}
+
+macro_rules! symbol_table {
+    ($table:ident, $read:expr, $write:expr) => {
+        impl<R: ?Sized> $table<R> {
+            /// Creates an empty table
+            pub fn new() -> Self {
+                $table {
+                    table: Default::default(),
+                    marker: PhantomData,
+                }
+            }
+        }
+
+        impl<R: ?Sized> Default for $table<R> {
+            fn default() -> Self {
+                $table::new()
+            }
+        }
+
+        impl<R: ?Sized> fmt::Debug for $table<R> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let table = $read(&self.table);
+                f.debug_list().entries(table.values.iter()).finish()
+            }
+        }
+
+        impl<R: ?Sized + TransparentRef<InnerRef = str> + AsRef<str>> SymbolTable<R> for $table<R> {
+            fn intern(&self, value: &str) -> Result<Symbol<R>, R::ValidationError> {
+                if let Some(symbol) = self.get(value) {
+                    return Ok(symbol);
+                }
+                R::validate(value)?;
+                let mut table = $write(&self.table);
+                let index = table.insert(value);
+                Ok(table.symbol(index))
+            }
+
+            fn intern_ref(&self, value: &R) -> Symbol<R> {
+                let mut table = $write(&self.table);
+                let index = table.insert(value.as_ref());
+                table.symbol(index)
+            }
+
+            fn get(&self, value: &str) -> Option<Symbol<R>> {
+                let table = $read(&self.table);
+                table.get(value).map(|index| table.symbol(index))
+            }
+
+            fn resolve(&self, symbol: Symbol<R>) -> &R {
+                let value = $read(&self.table).value(symbol);
+                // Values are never removed from the table and each one has
+                // its own allocation, so it lives as long as the table does.
+                // Every value in the table was validated before insertion.
+                #[allow(unsafe_code)]
+                unsafe { R::from_inner_unchecked(&*value) }
+            }
+
+            fn len(&self) -> usize {
+                $read(&self.table).values.len()
+            }
+        }
+    };
+}
+
+/// A single-threaded `SymbolTable`
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::{Interner, Symbol, SymbolTable};
+///
+/// fn validate_tag(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() {
+///         return Err("tags must not be empty");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Tag(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct TagRef(str) where validate = validate_tag, error = &'static str;
+/// }
+///
+/// pub type TagSym = Symbol<TagRef>;
+///
+/// # pub fn main() {
+/// let tags: Interner<TagRef> = Interner::new();
+/// let rust: TagSym = tags.intern("rust").unwrap();
+/// assert_eq!(rust, tags.intern("rust").unwrap());
+/// assert_eq!("rust", tags.resolve(rust));
+/// assert!(tags.intern("").is_err());
+/// # }
+/// ```
+pub struct Interner<R: ?Sized> {
+    table: RefCell<Table>,
+    marker: PhantomData<fn(&R)>,
+}
+
+symbol_table!(Interner, RefCell::borrow, RefCell::borrow_mut);
+
+/// A `SymbolTable` that can be shared between threads
+pub struct SyncInterner<R: ?Sized> {
+    table: RwLock<Table>,
+    marker: PhantomData<fn(&R)>,
+}
+
+symbol_table!(SyncInterner, read_table, write_table);
+
+// A panic while the lock is held cannot leave the table half updated, so a
+// poisoned lock is still safe to use.
+fn read_table(table: &RwLock<Table>) -> RwLockReadGuard<'_, Table> {
+    table.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write_table(table: &RwLock<Table>) -> RwLockWriteGuard<'_, Table> {
+    table.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A symbol paired with its table, which serializes as the symbol's value
+#[cfg(feature = "serde")]
+pub struct Interned<'a, T: 'a, R: ?Sized> {
+    table: &'a T,
+    symbol: Symbol<R>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: 'a, R: ?Sized> Interned<'a, T, R> {
+    /// Pairs `symbol` with the table it was interned in
+    pub fn new(table: &'a T, symbol: Symbol<R>) -> Self {
+        Interned { table, symbol }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: 'a, R: ?Sized> Clone for Interned<'a, T, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: 'a, R: ?Sized> Copy for Interned<'a, T, R> {}
+
+#[cfg(feature = "serde")]
+impl<'a, T: fmt::Debug + 'a, R: ?Sized> fmt::Debug for Interned<'a, T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Interned")
+            .field("table", self.table)
+            .field("symbol", &self.symbol)
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T, R> serde::Serialize for Interned<'a, T, R>
+where
+    T: SymbolTable<R> + 'a,
+    R: ?Sized + TransparentRef<InnerRef = str> + AsRef<str>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer {
+        serializer.serialize_str(self.table.resolve(self.symbol).as_ref())
+    }
+}
+
+/// Deserializes a string, validates it, and interns it into a table,
+/// producing its `Symbol`
+#[cfg(feature = "serde")]
+pub struct InternSeed<'a, T: 'a, R: ?Sized> {
+    table: &'a T,
+    marker: PhantomData<fn(&R)>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: 'a, R: ?Sized> InternSeed<'a, T, R> {
+    /// Creates a seed that interns into `table`
+    pub fn new(table: &'a T) -> Self {
+        InternSeed { table, marker: PhantomData }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: 'a, R: ?Sized> Clone for InternSeed<'a, T, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: 'a, R: ?Sized> Copy for InternSeed<'a, T, R> {}
+
+#[cfg(feature = "serde")]
+impl<'a, T: fmt::Debug + 'a, R: ?Sized> fmt::Debug for InternSeed<'a, T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InternSeed").field("table", self.table).finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, 'a, T, R> serde::de::DeserializeSeed<'de> for InternSeed<'a, T, R>
+where
+    T: SymbolTable<R> + 'a,
+    R: ?Sized + TransparentRef<InnerRef = str> + AsRef<str>,
+    R::ValidationError: fmt::Display,
+{
+    type Value = Symbol<R>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where D: serde::Deserializer<'de> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, 'a, T, R> serde::de::Visitor<'de> for InternSeed<'a, T, R>
+where
+    T: SymbolTable<R> + 'a,
+    R: ?Sized + TransparentRef<InnerRef = str> + AsRef<str>,
+    R::ValidationError: fmt::Display,
+{
+    type Value = Symbol<R>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        self.table.intern(value).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "serde")]
+    use bincode;
+    use std::sync::Arc;
+    use std::thread;
+    use super::{Interner, Symbol, SymbolTable, SyncInterner};
+
+    fn validate_word(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || !value.chars().all(char::is_alphabetic) {
+            return Err("not a word");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Word(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct WordRef(str) where validate = validate_word, error = &'static str;
+    }
+
+    type WordSym = Symbol<WordRef>;
+
+    #[test]
+    fn interning_deduplicates() {
+        let words: Interner<WordRef> = Interner::new();
+        let a: WordSym = words.intern("alpha").unwrap();
+        let b = words.intern("beta").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a, words.intern("alpha").unwrap());
+        assert_eq!(Some(b), words.get("beta"));
+        assert_eq!(None, words.get("gamma"));
+        assert_eq!(2, words.len());
+        assert_eq!("alpha", words.resolve(a));
+        assert_eq!("beta", words.resolve(b));
+    }
+
+    #[test]
+    #[should_panic(expected = "symbol was not handed out by this table")]
+    fn symbols_only_resolve_in_their_table() {
+        let words: Interner<WordRef> = Interner::new();
+        let other: Interner<WordRef> = Interner::new();
+        let symbol = words.intern("alpha").unwrap();
+        other.intern("beta").unwrap();
+        other.resolve(symbol);
+    }
+
+    #[test]
+    fn only_valid_values_are_interned() {
+        let words: Interner<WordRef> = Interner::new();
+        assert_eq!(Err("not a word"), words.intern("two words"));
+        assert!(words.is_empty());
+        let word = WordRef::try_as_ref("word").unwrap();
+        let symbol = words.intern_ref(word);
+        assert_eq!(word, words.resolve(symbol));
+    }
+
+    #[test]
+    fn resolved_values_outlive_later_interning() {
+        let words: Interner<WordRef> = Interner::new();
+        let first = words.resolve(words.intern("first").unwrap());
+        let many: String = (0..1000).map(|i| if i % 2 == 0 { 'a' } else { 'b' }).collect();
+        for end in 1..many.len() {
+            words.intern(&many[..end]).unwrap();
+        }
+        assert_eq!("first", first);
+    }
+
+    #[test]
+    fn sync_interner_is_shared_between_threads() {
+        let words: Arc<SyncInterner<WordRef>> = Arc::new(SyncInterner::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let words = Arc::clone(&words);
+                thread::spawn(move || words.intern("shared").unwrap())
+            })
+            .collect();
+        let symbols: Vec<WordSym> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(symbols.iter().all(|s| *s == symbols[0]));
+        assert_eq!(1, words.len());
+        assert_eq!("shared", words.resolve(symbols[0]));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn symbols_serialize_as_values() {
+        use super::{InternSeed, Interned};
+        use serde::de::{DeserializeSeed, IntoDeserializer};
+        use serde::de::value::Error;
+
+        let words: Interner<WordRef> = Interner::new();
+        let symbol = words.intern("serde").unwrap();
+        let serialized = bincode::serialize(&Interned::new(&words, symbol))
+            .expect("serialization to succeed");
+        assert_eq!(bincode::serialize("serde").unwrap(), serialized);
+
+        let other: Interner<WordRef> = Interner::new();
+        let deserializer = IntoDeserializer::<Error>::into_deserializer("serde");
+        let value = InternSeed::new(&other)
+            .deserialize(deserializer)
+            .expect("deserialization to succeed");
+        assert_eq!("serde", other.resolve(value));
+
+        let invalid = IntoDeserializer::<Error>::into_deserializer("not serde");
+        assert!(InternSeed::new(&other).deserialize(invalid).is_err());
+    }
+}
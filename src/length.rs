@@ -0,0 +1,413 @@
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "graphemes")]
+use unicode_segmentation::UnicodeSegmentation;
+
+use stream::{StreamError, Utf8Chunks};
+use traits::ValidatorState;
+
+/// The unit in which a `LengthLimit` measures values.
+///
+/// Matches on this must have a wildcard arm, since `Graphemes` is only
+/// defined with the `graphemes` feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LengthUnit {
+    /// UTF-8 bytes, as returned by `str::len`
+    Bytes,
+    /// Unicode scalar values, as returned by `str::chars`
+    Chars,
+    /// Extended grapheme clusters, which are what users perceive as single
+    /// characters
+    #[cfg(feature = "graphemes")]
+    Graphemes,
+    /// UTF-16 code units, in which JavaScript strings and many databases
+    /// measure lengths
+    Utf16,
+}
+
+impl LengthUnit {
+    /// Measures `value` in this unit
+    pub fn count(self, value: &str) -> usize {
+        match self {
+            LengthUnit::Bytes => value.len(),
+            LengthUnit::Chars => count_chars(value),
+            #[cfg(feature = "graphemes")]
+            LengthUnit::Graphemes => value.graphemes(true).count(),
+            LengthUnit::Utf16 => count_chars(value) + count_bytes(value, four_byte_leads),
+        }
+    }
+}
+
+// Counts characters and UTF-16 code units eight bytes at a time in a `u64`
+// (SWAR), rather than decoding each character, which needs no SIMD
+// instructions and so works alike on every target.
+// Every character has one byte that is not a continuation byte, and those
+// outside the Basic Multilingual Plane, which take two UTF-16 code units,
+// start with a byte of the form `11110xxx`.
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+fn count_chars(value: &str) -> usize {
+    if value.is_ascii() {
+        return value.len();
+    }
+    value.len() - count_bytes(value, continuations)
+}
+
+fn count_bytes(value: &str, matches: fn(u64) -> u64) -> usize {
+    let chunks = value.as_bytes().chunks_exact(8);
+    let rest = chunks.remainder();
+    let mut count = 0;
+    for chunk in chunks {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        count += matches(u64::from_ne_bytes(word)).count_ones() as usize;
+    }
+    for &byte in rest {
+        count += matches(u64::from(byte)).count_ones() as usize;
+    }
+    count
+}
+
+// Sets the high bit of each byte of the form `10xxxxxx`.
+fn continuations(word: u64) -> u64 {
+    word & !(word << 1) & HIGH_BITS
+}
+
+// Sets the high bit of each byte of the form `1111xxxx`.
+fn four_byte_leads(word: u64) -> u64 {
+    word & (word << 1) & (word << 2) & (word << 3) & HIGH_BITS
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            LengthUnit::Bytes => "bytes",
+            LengthUnit::Chars => "characters",
+            #[cfg(feature = "graphemes")]
+            LengthUnit::Graphemes => "grapheme clusters",
+            LengthUnit::Utf16 => "UTF-16 code units",
+        })
+    }
+}
+
+/// A maximum length for values, measured in a chosen `LengthUnit`, for use
+/// from `NewTypeRef::validate`.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::{LengthError, LengthLimit, LengthUnit};
+///
+/// const DISPLAY_NAME_LIMIT: LengthLimit = LengthLimit::chars(5);
+///
+/// fn validate_display_name(value: &str) -> Result<(), LengthError> {
+///     DISPLAY_NAME_LIMIT.validate(value)
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct DisplayName(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct DisplayNameRef(str)
+///         where validate = validate_display_name, error = LengthError;
+/// }
+///
+/// # pub fn main() {
+/// assert!(DisplayName::try_from("ĝuste").is_ok());
+/// let error = DisplayName::try_from("ĝustas").unwrap_err();
+/// assert_eq!(LengthUnit::Chars, error.unit());
+/// assert_eq!(1, error.excess());
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LengthLimit {
+    unit: LengthUnit,
+    max: usize,
+}
+
+impl LengthLimit {
+    /// Limits values to `max` units of `unit`
+    pub const fn new(unit: LengthUnit, max: usize) -> Self {
+        LengthLimit { unit, max }
+    }
+
+    /// Limits values to `max` UTF-8 bytes
+    pub const fn bytes(max: usize) -> Self {
+        LengthLimit::new(LengthUnit::Bytes, max)
+    }
+
+    /// Limits values to `max` Unicode scalar values
+    pub const fn chars(max: usize) -> Self {
+        LengthLimit::new(LengthUnit::Chars, max)
+    }
+
+    /// Limits values to `max` extended grapheme clusters
+    #[cfg(feature = "graphemes")]
+    pub const fn graphemes(max: usize) -> Self {
+        LengthLimit::new(LengthUnit::Graphemes, max)
+    }
+
+    /// Limits values to `max` UTF-16 code units
+    pub const fn utf16(max: usize) -> Self {
+        LengthLimit::new(LengthUnit::Utf16, max)
+    }
+
+    /// The unit in which values are measured
+    pub fn unit(&self) -> LengthUnit {
+        self.unit
+    }
+
+    /// The greatest allowed length
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Checks that `value` is no longer than the limit
+    pub fn validate(&self, value: &str) -> Result<(), LengthError> {
+        // Every unit counts at most one per byte, so short values need not
+        // be measured.
+        if value.len() <= self.max {
+            return Ok(());
+        }
+        let length = self.unit.count(value);
+        if length <= self.max {
+            return Ok(());
+        }
+        Err(LengthError { limit: *self, length })
+    }
+
+    /// Creates a `ValidatorState` that checks a value against the limit one
+    /// chunk at a time
+    pub fn stream(&self) -> LengthState {
+        LengthState {
+            limit: *self,
+            length: 0,
+            chunks: Utf8Chunks::default(),
+            #[cfg(feature = "graphemes")]
+            grapheme: String::new(),
+        }
+    }
+}
+
+/// Checks a value against a `LengthLimit` one chunk at a time, as created by
+/// `LengthLimit::stream`.
+///
+/// Values are rejected as soon as they exceed the limit, so the length
+/// reported by the error only counts the chunks fed up to that point.
+///
+/// ```
+/// use new_type_derive::{LengthLimit, ValidatorState};
+///
+/// let mut state = LengthLimit::chars(5).stream();
+/// state.feed(&"ĝuste".as_bytes()[..1]).unwrap();
+/// state.feed(&"ĝuste".as_bytes()[1..]).unwrap();
+/// assert!(state.feed(b"!").is_err());
+/// ```
+// Only `Clone`, since the state holds a `String` with the `graphemes` feature.
+#[allow(missing_copy_implementations)]
+#[derive(Clone, Debug)]
+pub struct LengthState {
+    limit: LengthLimit,
+    length: usize,
+    chunks: Utf8Chunks,
+    // The last grapheme cluster seen, which later chunks may extend.
+    #[cfg(feature = "graphemes")]
+    grapheme: String,
+}
+
+impl LengthState {
+    fn check(&self, length: usize) -> Result<(), LengthError> {
+        if length <= self.limit.max {
+            Ok(())
+        } else {
+            Err(LengthError { limit: self.limit, length })
+        }
+    }
+
+    #[cfg(feature = "graphemes")]
+    fn count(&mut self, piece: &str) -> usize {
+        if self.limit.unit != LengthUnit::Graphemes {
+            return self.limit.unit.count(piece);
+        }
+        self.grapheme.push_str(piece);
+        let mut completed = 0;
+        let mut last = 0;
+        for (i, _) in self.grapheme.grapheme_indices(true).skip(1) {
+            completed += 1;
+            last = i;
+        }
+        self.grapheme.drain(..last);
+        completed
+    }
+
+    #[cfg(not(feature = "graphemes"))]
+    fn count(&mut self, piece: &str) -> usize {
+        self.limit.unit.count(piece)
+    }
+}
+
+impl ValidatorState for LengthState {
+    type Error = StreamError<LengthError>;
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        let mut chunks = self.chunks;
+        let result = chunks.feed(chunk, |piece| {
+            self.length += self.count(piece);
+            self.check(self.length)
+        });
+        self.chunks = chunks;
+        result
+    }
+
+    fn finish(self) -> Result<(), Self::Error> {
+        self.chunks.finish()?;
+        #[cfg(feature = "graphemes")]
+        let length = self.length + if self.grapheme.is_empty() { 0 } else { 1 };
+        #[cfg(not(feature = "graphemes"))]
+        let length = self.length;
+        self.check(length).map_err(StreamError::Invalid)
+    }
+}
+
+/// The error returned when a value is longer than a `LengthLimit` allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LengthError {
+    limit: LengthLimit,
+    length: usize,
+}
+
+impl LengthError {
+    /// The unit in which the limit was exceeded
+    pub fn unit(&self) -> LengthUnit {
+        self.limit.unit
+    }
+
+    /// The greatest allowed length
+    pub fn max(&self) -> usize {
+        self.limit.max
+    }
+
+    /// The length of the value
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// How far the value exceeded the limit
+    pub fn excess(&self) -> usize {
+        self.length - self.limit.max
+    }
+}
+
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "value is {} {} long, {} more than the limit of {}",
+            self.length,
+            self.limit.unit,
+            self.excess(),
+            self.limit.max,
+        )
+    }
+}
+
+impl Error for LengthError {}
+
+#[cfg(test)]
+mod test {
+    use super::{LengthLimit, LengthUnit};
+    use stream::StreamError;
+    use traits::ValidatorState;
+
+    const FAMILY: &str = "👨‍👩‍👧";
+
+    #[test]
+    fn counts_in_each_unit() {
+        assert_eq!(6, LengthUnit::Bytes.count("ĝuste"));
+        assert_eq!(5, LengthUnit::Chars.count("ĝuste"));
+        assert_eq!(5, LengthUnit::Utf16.count("ĝuste"));
+        assert_eq!(5, LengthUnit::Chars.count(FAMILY));
+        assert_eq!(8, LengthUnit::Utf16.count(FAMILY));
+        assert_eq!(18, LengthUnit::Bytes.count(FAMILY));
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn counts_graphemes() {
+        assert_eq!(1, LengthUnit::Graphemes.count(FAMILY));
+        assert_eq!(2, LengthUnit::Graphemes.count("e\u{301}e"));
+        assert_eq!(Ok(()), LengthLimit::graphemes(1).validate(FAMILY));
+    }
+
+    #[test]
+    fn reports_the_excess() {
+        assert_eq!(Ok(()), LengthLimit::chars(5).validate("ĝuste"));
+        let error = LengthLimit::bytes(5).validate("ĝuste").unwrap_err();
+        assert_eq!(LengthUnit::Bytes, error.unit());
+        assert_eq!(6, error.length());
+        assert_eq!(1, error.excess());
+        assert_eq!("value is 6 bytes long, 1 more than the limit of 5", error.to_string());
+        let error = LengthLimit::utf16(4).validate(FAMILY).unwrap_err();
+        assert_eq!(4, error.excess());
+        assert_eq!("value is 8 UTF-16 code units long, 4 more than the limit of 4", error.to_string());
+    }
+
+    proptest! {
+        #[test]
+        fn units_never_exceed_bytes(ref s in ".{0,16}") {
+            let bytes = LengthUnit::Bytes.count(s);
+            assert!(LengthUnit::Chars.count(s) <= bytes);
+            assert!(LengthUnit::Utf16.count(s) <= bytes);
+            assert_eq!(s.encode_utf16().count(), LengthUnit::Utf16.count(s));
+            assert_eq!(s.chars().count(), LengthUnit::Chars.count(s));
+        }
+    }
+
+    fn stream(limit: LengthLimit, value: &str, size: usize) -> Result<(), StreamError<super::LengthError>> {
+        let mut state = limit.stream();
+        for chunk in value.as_bytes().chunks(size) {
+            state.feed(chunk)?;
+        }
+        state.finish()
+    }
+
+    #[test]
+    fn streams_reject_values_early() {
+        let mut state = LengthLimit::bytes(4).stream();
+        assert_eq!(Ok(()), state.feed(b"abc"));
+        match state.feed(b"de") {
+            Err(StreamError::Invalid(error)) => assert_eq!(5, error.length()),
+            other => panic!("expected the limit to be exceeded, got {:?}", other),
+        }
+        assert_eq!(Ok(()), stream(LengthLimit::utf16(8), FAMILY, 1));
+        assert!(stream(LengthLimit::utf16(7), FAMILY, 1).is_err());
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn streams_count_graphemes_split_across_chunks() {
+        assert_eq!(Ok(()), stream(LengthLimit::graphemes(1), FAMILY, 1));
+        assert!(stream(LengthLimit::graphemes(1), "e\u{301}e", 1).is_err());
+        assert_eq!(Ok(()), stream(LengthLimit::graphemes(2), "e\u{301}e", 1));
+    }
+
+    proptest! {
+        #[test]
+        fn streams_agree_with_validate(ref s in "(\\PC|\u{200d}|\u{301}){0,8}", size in 1usize..6, max in 0usize..10) {
+            let limits = [
+                LengthLimit::bytes(max),
+                LengthLimit::chars(max),
+                LengthLimit::utf16(max),
+                #[cfg(feature = "graphemes")]
+                LengthLimit::graphemes(max),
+            ];
+            for &limit in &limits {
+                assert_eq!(limit.validate(s).is_ok(), stream(limit, s, size).is_ok(), "{:?}", limit);
+            }
+        }
+    }
+}
@@ -23,18 +23,73 @@ extern crate lazy_static;
 #[cfg(test)]
 #[macro_use]
 extern crate proptest;
-#[cfg(all(feature = "serde", test))]
+#[cfg(feature = "serde")]
 extern crate serde;
 #[cfg(all(feature = "serde", test))]
 #[macro_use]
 extern crate serde_derive;
+#[cfg(all(feature = "serde", test))]
+extern crate serde_test;
 #[cfg(test)]
 #[macro_use]
 extern crate static_assertions;
+#[cfg(feature = "normalization")]
+extern crate unicode_normalization;
+#[cfg(feature = "confusables")]
+extern crate unicode_security;
+#[cfg(feature = "graphemes")]
+extern crate unicode_segmentation;
 
 mod traits;
 
-pub use traits::NewTypeRef;
+pub use arena::NewTypeArena;
+pub use charset::{Charset, CharsetError, CharsetState};
+pub use compare::{AsciiCaseInsensitive, CaseInsensitive, NaturalOrder};
+#[cfg(feature = "confusables")]
+pub use confusables::{skeleton, validate_confusables, ConfusableError, ConfusablesState, Skeleton};
+pub use interner::{Interner, Symbol, SymbolTable, SyncInterner};
+pub use length::{LengthError, LengthLimit, LengthState, LengthUnit};
+pub use mask::{Mask, Masked};
+#[cfg(feature = "normalization")]
+pub use normalization::{to_nfc, to_nfkc, validate_nfc, validate_nfkc, NormalFormState, NormalizationError, Normalize};
+pub use secret::{constant_time_eq, zeroize, Zeroize};
+pub use stream::{StreamError, Utf8Chunks};
+#[cfg(feature = "serde")]
+pub use interner::{InternSeed, Interned};
+pub use traits::{Comparison, NewTypeRef, Refines, StreamValidate, SubstringClosed, TransparentOwned, TransparentRef, TryFromStr, ValidatorState};
 
 #[macro_use]
 mod new_type_pair;
+
+#[macro_use]
+mod new_type_ref;
+
+#[macro_use]
+mod new_type_owned;
+
+#[macro_use]
+mod new_type_enum;
+
+#[macro_use]
+mod new_type_refines;
+
+#[macro_use]
+mod new_type_comparable;
+
+#[macro_use]
+mod new_type_format;
+
+mod arena;
+mod charset;
+mod compare;
+#[cfg(feature = "confusables")]
+mod confusables;
+mod interner;
+mod length;
+mod mask;
+#[cfg(feature = "normalization")]
+mod normalization;
+mod secret;
+mod stream;
+#[cfg(feature = "serde")]
+pub mod trusted_unchecked;
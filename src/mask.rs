@@ -0,0 +1,156 @@
+use std::fmt::{self, Write};
+
+/// How a value is masked when displayed through `Masked`, chosen for each
+/// reference type by `NewTypeRef::MASK`.
+#[derive(Clone, Copy, Debug)]
+pub enum Mask {
+    /// Replaces every character with `*`
+    All,
+    /// Keeps the given number of characters at the start and at the end of
+    /// the value, replacing the rest with `*`. Values too short to hide
+    /// anything are masked entirely.
+    Keep {
+        /// The number of leading characters to keep
+        first: usize,
+        /// The number of trailing characters to keep
+        last: usize,
+    },
+    /// Writes the masked form of the value with a custom function
+    With(fn(&str, &mut fmt::Formatter) -> fmt::Result),
+}
+
+/// Displays a value in masked form, such as `************1234`, so that it
+/// can be logged without revealing it.
+///
+/// This is returned by the `masked` method of reference types, and its
+/// `Debug` output is masked as well.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::{Mask, NewTypeRef};
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct CardNumber(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct CardNumberRef(str);
+/// }
+///
+/// impl NewTypeRef for CardNumberRef {
+///     type Owned = CardNumber;
+///     type InnerRef = str;
+///     type ValidationError = &'static str;
+///
+///     const MASK: Mask = Mask::Keep { first: 0, last: 4 };
+///
+///     fn validate(value: &str) -> Result<(), Self::ValidationError> {
+///         if value.len() != 16 || !value.bytes().all(|b| b.is_ascii_digit()) {
+///             return Err("card numbers have sixteen digits");
+///         }
+///         Ok(())
+///     }
+///
+///     fn to_owned(&self) -> Self::Owned {
+///         CardNumber { inner: self.inner.into() }
+///     }
+/// }
+///
+/// # pub fn main() {
+/// let card = CardNumber::try_from("4111111111111234").unwrap();
+/// assert_eq!("************1234", card.masked().to_string());
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct Masked<'a> {
+    value: &'a str,
+    mask: Mask,
+}
+
+impl<'a> Masked<'a> {
+    /// Masks `value` with `mask`
+    pub fn new(value: &'a str, mask: Mask) -> Self {
+        Masked { value, mask }
+    }
+}
+
+fn write_stars(count: usize, f: &mut fmt::Formatter) -> fmt::Result {
+    for _ in 0..count {
+        f.write_char('*')?;
+    }
+    Ok(())
+}
+
+impl<'a> fmt::Display for Masked<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mask {
+            Mask::All => write_stars(self.value.chars().count(), f),
+            Mask::Keep { first, last } => {
+                let count = self.value.chars().count();
+                if count <= first.saturating_add(last) {
+                    return write_stars(count, f);
+                }
+                let hidden = count - first - last;
+                let mut chars = self.value.chars();
+                for c in chars.by_ref().take(first) {
+                    f.write_char(c)?;
+                }
+                write_stars(hidden, f)?;
+                for c in chars.skip(hidden) {
+                    f.write_char(c)?;
+                }
+                Ok(())
+            }
+            Mask::With(mask) => mask(self.value, f),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Masked<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mask, Masked};
+    use std::fmt;
+
+    fn mask_domain(value: &str, f: &mut fmt::Formatter) -> fmt::Result {
+        match value.find('@') {
+            Some(at) => write!(f, "***{}", &value[at..]),
+            None => f.write_str("***"),
+        }
+    }
+
+    #[test]
+    fn masks_everything_by_default() {
+        assert_eq!("*****", Masked::new("ĉiuj!", Mask::All).to_string());
+        assert_eq!("", Masked::new("", Mask::All).to_string());
+    }
+
+    #[test]
+    fn keeps_characters_at_either_end() {
+        let mask = Mask::Keep { first: 2, last: 3 };
+        assert_eq!("+4******789", Masked::new("+4412345789", mask).to_string());
+        assert_eq!("*****", Masked::new("12345", mask).to_string());
+        assert_eq!("ĉu*ĝa", Masked::new("ĉuxĝa", Mask::Keep { first: 2, last: 2 }).to_string());
+    }
+
+    #[test]
+    fn masks_everything_when_keeping_more_than_fits() {
+        assert_eq!("*****", Masked::new("12345", Mask::Keep { first: usize::MAX, last: 1 }).to_string());
+        assert_eq!("*****", Masked::new("12345", Mask::Keep { first: usize::MAX, last: usize::MAX }).to_string());
+    }
+
+    #[test]
+    fn masks_with_custom_functions() {
+        let masked = Masked::new("ada@example.com", Mask::With(mask_domain));
+        assert_eq!("***@example.com", masked.to_string());
+        assert_eq!("***@example.com", format!("{:?}", masked));
+    }
+}
@@ -0,0 +1,173 @@
+#[macro_export]
+/// Implements `PartialEq` and `PartialOrd` between the owned and reference
+/// types of two pairs, each given as `Owned(Ref)`, whose reference types
+/// must wrap the same type.
+///
+/// This covers the same combinations of owned types, reference types, and
+/// references to them that `new_type_pair!` implements against the wrapped
+/// type, in both directions. Values are compared by the values they wrap,
+/// byte-wise for `str`, regardless of the `compare` option of either pair.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// fn validate_user_name(value: &str) -> Result<(), &'static str> {
+///     if !value.bytes().all(|b| b.is_ascii_lowercase()) {
+///         return Err("user names are lowercase");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct UserName(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct UserNameRef(str) where validate = validate_user_name, error = &'static str;
+///
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct DisplayName(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct DisplayNameRef(str);
+/// }
+///
+/// # impl new_type_derive::NewTypeRef for DisplayNameRef {
+/// #     type Owned = DisplayName;
+/// #     type InnerRef = str;
+/// #     type ValidationError = &'static str;
+/// #     fn to_owned(&self) -> DisplayName {
+/// #         DisplayName { inner: self.inner.into() }
+/// #     }
+/// # }
+/// #
+/// new_type_comparable!(UserName(UserNameRef), DisplayName(DisplayNameRef));
+///
+/// # pub fn main() {
+/// let user = UserName::try_from("ada").unwrap();
+/// let display = DisplayName::try_from("Ada").unwrap();
+/// assert!(user != display);
+/// assert!(*display < user);
+/// assert_eq!(user, DisplayNameRef::try_as_ref("ada").unwrap());
+/// # }
+/// ```
+macro_rules! new_type_comparable {
+    ($a:ident($ar:ident), $b:ident($br:ident)) => {
+        $crate::new_type_comparable!(@one_way $a $ar, $b $br);
+        $crate::new_type_comparable!(@one_way $b $br, $a $ar);
+    };
+    ($a:ident($ar:ident), $b:ident($br:ident),) => {
+        $crate::new_type_comparable!($a($ar), $b($br));
+    };
+
+    (@one_way $a:ident $ar:ident, $b:ident $br:ident) => {
+        $crate::new_type_comparable!(@cmp [] $a, $b, $ar, $br);
+        $crate::new_type_comparable!(@cmp [] $a, $br, $ar, $br);
+        $crate::new_type_comparable!(@cmp ['a] $a, &'a $br, $ar, $br);
+        $crate::new_type_comparable!(@cmp [] $ar, $b, $ar, $br);
+        $crate::new_type_comparable!(@cmp ['a] &'a $ar, $b, $ar, $br);
+        $crate::new_type_comparable!(@cmp [] $ar, $br, $ar, $br);
+        $crate::new_type_comparable!(@cmp ['a] $ar, &'a $br, $ar, $br);
+        $crate::new_type_comparable!(@cmp ['a] &'a $ar, $br, $ar, $br);
+    };
+
+    // Compares two types by the values wrapped by the reference types `$ar`
+    // and `$br`, which each side converts to.
+    (@cmp [$($lt:tt)*] $lhs:ty, $rhs:ty, $ar:ident, $br:ident) => {
+impl<$($lt)*> ::std::cmp::PartialEq<$rhs> for $lhs {
+    #[inline]
+    fn eq(&self, rhs: &$rhs) -> bool {
+        let lhs: &$ar = ::std::convert::AsRef::as_ref(self);
+        let rhs: &$br = ::std::convert::AsRef::as_ref(rhs);
+        ::std::convert::AsRef::<<$ar as $crate::NewTypeRef>::InnerRef>::as_ref(lhs) == ::std::convert::AsRef::<<$br as $crate::NewTypeRef>::InnerRef>::as_ref(rhs)
+    }
+}
+
+impl<$($lt)*> ::std::cmp::PartialOrd<$rhs> for $lhs {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$rhs) -> Option<::std::cmp::Ordering> {
+        let lhs: &$ar = ::std::convert::AsRef::as_ref(self);
+        let rhs: &$br = ::std::convert::AsRef::as_ref(rhs);
+        ::std::cmp::PartialOrd::partial_cmp(
+            ::std::convert::AsRef::<<$ar as $crate::NewTypeRef>::InnerRef>::as_ref(lhs),
+            ::std::convert::AsRef::<<$br as $crate::NewTypeRef>::InnerRef>::as_ref(rhs),
+        )
+    }
+}
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+    use AsciiCaseInsensitive;
+
+    fn validate_user_name(value: &str) -> Result<(), &'static str> {
+        if !value.bytes().all(|b| b.is_ascii_lowercase()) {
+            return Err("invalid user name");
+        }
+        Ok(())
+    }
+
+    fn validate_display_name(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() {
+            return Err("invalid display name");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct UserName(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct UserNameRef(str) where validate = validate_user_name, error = &'static str;
+
+        #[derive(Clone, Debug)]
+        pub struct DisplayName(String);
+
+        #[derive(Debug)]
+        pub struct DisplayNameRef(str)
+        where
+            validate = validate_display_name,
+            error = &'static str,
+            compare = AsciiCaseInsensitive;
+    }
+
+    new_type_comparable!(UserName(UserNameRef), DisplayName(DisplayNameRef));
+
+    #[test]
+    fn compares_every_form() {
+        let user = UserName::try_from("ada").unwrap();
+        let user_ref: &UserNameRef = &user;
+        let display = DisplayName::try_from("ada").unwrap();
+        let display_ref: &DisplayNameRef = &display;
+        assert_eq!(user, display);
+        assert_eq!(user, *display_ref);
+        assert_eq!(user, display_ref);
+        assert_eq!(*user_ref, display);
+        assert_eq!(user_ref, display);
+        assert_eq!(*user_ref, *display_ref);
+        assert_eq!(*user_ref, display_ref);
+        assert_eq!(user_ref, *display_ref);
+        assert_eq!(display, user);
+        assert_eq!(display_ref, user);
+        assert_eq!(*display_ref, *user_ref);
+        assert_eq!(Some(Ordering::Equal), display_ref.partial_cmp(user_ref));
+    }
+
+    #[test]
+    fn compares_wrapped_values_byte_wise() {
+        let user = UserName::try_from("ada").unwrap();
+        let display = DisplayName::try_from("Ada").unwrap();
+        assert_eq!(display, "ADA");
+        assert!(user != display);
+        assert_eq!(Some(Ordering::Greater), user.partial_cmp(&display));
+        assert_eq!(Some(Ordering::Less), (*display).partial_cmp(&user));
+        assert!(*display < *user);
+    }
+}
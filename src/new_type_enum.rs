@@ -0,0 +1,242 @@
+#[macro_export]
+/// Creates an enum over a known vocabulary of values of a reference type,
+/// with an `Other` variant holding any other valid value as the owned type.
+///
+/// Each known variant is declared along with the string it stands for, which
+/// must be valid for the reference type. The strings are all validated the
+/// first time a known variant is viewed as the reference type, which panics
+/// if any of them is not valid. Converting from a `&FooRef` or a
+/// `Foo` picks the matching known variant, comparing through the reference
+/// type's `PartialEq<str>`, and only falls back to `Other` for values that
+/// match no known variant. The enum implements `AsRef<FooRef>`, so every value
+/// can be turned back into a reference, and with the `serde` feature it
+/// serializes as that reference and deserializes through the owned type, so
+/// that unknown values survive a round trip.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Region(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct RegionRef(str) where validate = validate_region, error = &'static str;
+/// }
+///
+/// fn validate_region(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() || !value.bytes().all(|b| b.is_ascii_lowercase() || b == b'-') {
+///         return Err("regions are lowercase words separated by dashes");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_enum! {
+///     #[derive(Clone, Debug, PartialEq, Eq)]
+///     pub enum KnownRegion(RegionRef) {
+///         UsEast = "us-east",
+///         EuWest = "eu-west",
+///     }
+/// }
+///
+/// # pub fn main() {
+/// let region = RegionRef::try_as_ref("eu-west").unwrap();
+/// assert_eq!(KnownRegion::EuWest, KnownRegion::from(region));
+/// let region = Region::try_from("ap-south").unwrap();
+/// assert_eq!(KnownRegion::Other(region.clone()), KnownRegion::from(region));
+/// assert_eq!("us-east", KnownRegion::UsEast.as_ref());
+/// # }
+/// ```
+macro_rules! new_type_enum {
+    ($(
+        $(#[$emeta:meta])*
+        pub enum $etype:ident($rtype:ident) {
+            $(
+                $(#[$vmeta:meta])*
+                $variant:ident = $value:expr
+            ),+ $(,)*
+        }
+    )+) => {$(
+$(#[$emeta])*
+pub enum $etype {
+    $(
+        $(#[$vmeta])*
+        $variant,
+    )+
+    /// A valid value that matches none of the known variants
+    Other(<$rtype as $crate::NewTypeRef>::Owned),
+}
+
+impl $etype {
+    /// Returns whether the value is one of the known variants
+    #[inline]
+    pub fn is_known(&self) -> bool {
+        match *self {
+            $etype::Other(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Converts the value into the owned type
+    pub fn into_owned(self) -> <$rtype as $crate::NewTypeRef>::Owned {
+        match self {
+            $etype::Other(value) => value,
+            known => $crate::NewTypeRef::to_owned(::std::convert::AsRef::<$rtype>::as_ref(&known)),
+        }
+    }
+
+    // Validates every known value the first time one is used, so that the
+    // others can be viewed as the reference type without validating them.
+    #[inline]
+    fn known(value: &'static str) -> &'static $rtype {
+        static CHECKED: ::std::sync::Once = ::std::sync::Once::new();
+        CHECKED.call_once(|| {
+            $(
+                if <$rtype as $crate::NewTypeRef>::validate($value).is_err() {
+                    panic!("known variant {:?} is not a valid {}", $value, stringify!($rtype));
+                }
+            )+
+        });
+        #[allow(unsafe_code)] unsafe { <$rtype as $crate::TransparentRef>::from_inner_unchecked(value) }
+    }
+}
+
+impl ::std::convert::AsRef<$rtype> for $etype {
+    fn as_ref(&self) -> &$rtype {
+        match *self {
+            $( $etype::$variant => $etype::known($value), )+
+            $etype::Other(ref value) => value.as_ref(),
+        }
+    }
+}
+
+impl<'a> From<&'a $rtype> for $etype {
+    fn from(value: &'a $rtype) -> Self {
+        $(
+            if *value == *$value {
+                return $etype::$variant;
+            }
+        )+
+        $etype::Other($crate::NewTypeRef::to_owned(value))
+    }
+}
+
+impl From<<$rtype as $crate::NewTypeRef>::Owned> for $etype {
+    fn from(value: <$rtype as $crate::NewTypeRef>::Owned) -> Self {
+        match <$etype as From<&$rtype>>::from(value.as_ref()) {
+            $etype::Other(_) => $etype::Other(value),
+            known => known,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for $etype {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ::serde::Serialize::serialize(::std::convert::AsRef::<$rtype>::as_ref(self), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for $etype {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = <<$rtype as $crate::NewTypeRef>::Owned as ::serde::Deserialize<'de>>::deserialize(deserializer)?;
+        Ok(From::from(value))
+    }
+}
+    )+};
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "serde")]
+    use bincode;
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Region(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct RegionRef(str) where validate = validate_region, error = &'static str;
+    }
+
+    fn validate_region(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_lowercase() || b == b'-') {
+            return Err("invalid region");
+        }
+        Ok(())
+    }
+
+    new_type_enum! {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        /// Regions known at compile time
+        pub enum KnownRegion(RegionRef) {
+            /// The eastern United States
+            UsEast = "us-east",
+            EuWest = "eu-west",
+            EuCentral = "eu-central",
+        }
+
+        #[derive(Debug)]
+        pub enum MisspeltRegion(RegionRef) {
+            UsEast = "us-east",
+            EuWest = "EU-WEST",
+        }
+    }
+
+    #[test]
+    fn picks_known_variants() {
+        let region = RegionRef::try_as_ref("eu-central").unwrap();
+        assert_eq!(KnownRegion::EuCentral, KnownRegion::from(region));
+        assert_eq!(KnownRegion::UsEast, KnownRegion::from(Region::try_from("us-east").unwrap()));
+        assert!(KnownRegion::UsEast.is_known());
+        assert_eq!("eu-west", KnownRegion::EuWest.as_ref());
+        assert_eq!("eu-west", KnownRegion::EuWest.into_owned());
+    }
+
+    #[test]
+    fn keeps_other_values() {
+        let region = Region::try_from("ap-south").unwrap();
+        let known = KnownRegion::from(&*region);
+        assert_eq!(KnownRegion::Other(region.clone()), known);
+        assert!(!known.is_known());
+        assert_eq!(&*region, known.as_ref());
+        assert_eq!(region, known.into_owned());
+    }
+
+    #[test]
+    #[should_panic(expected = "known variant \"EU-WEST\" is not a valid RegionRef")]
+    fn rejects_invalid_known_values() {
+        assert!(MisspeltRegion::UsEast.is_known());
+        MisspeltRegion::UsEast.into_owned();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn roundtrips_through_serde() {
+        for value in &["us-east", "ap-south"] {
+            let region = KnownRegion::from(RegionRef::try_as_ref(value).unwrap());
+            let serialized = bincode::serialize(&region).expect("serialization to succeed");
+            assert_eq!(bincode::serialize(value).unwrap(), serialized);
+            let deserialized: KnownRegion =
+                bincode::deserialize(&serialized).expect("deserialization to succeed");
+            assert_eq!(region, deserialized);
+        }
+        let invalid = bincode::serialize("AP-SOUTH").unwrap();
+        assert!(bincode::deserialize::<KnownRegion>(&invalid).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn converts_without_losing_values(ref s in "[a-z-]{1,10}") {
+            let region = RegionRef::try_as_ref(s).unwrap();
+            let known = KnownRegion::from(region);
+            assert_eq!(region, known.as_ref());
+            assert_eq!(known.is_known(), ["us-east", "eu-west", "eu-central"].contains(&s.as_str()));
+        }
+    }
+}
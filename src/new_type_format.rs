@@ -0,0 +1,82 @@
+#[macro_export]
+/// Formats a value like `format!` and validates it as the given owned type
+/// of a pair, returning the owned value or the validation error.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// fn validate_ticket(value: &str) -> Result<(), &'static str> {
+///     if value.len() > 8 || !value.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+///         return Err("tickets are short and alphanumeric");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Ticket(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct TicketRef(str) where validate = validate_ticket, error = &'static str;
+/// }
+///
+/// # pub fn main() {
+/// let project = "WEB";
+/// assert_eq!("WEB-42", new_type_format!(Ticket, "{}-{}", project, 42).unwrap());
+/// assert!(new_type_format!(Ticket, "{} {}", project, 42).is_err());
+/// # }
+/// ```
+macro_rules! new_type_format {
+    ($otype:ty, $($args:tt)+) => {
+        <$otype>::try_from(::std::format!($($args)+))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    fn validate_slug(value: &str) -> Result<(), &'static str> {
+        if !value.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-') {
+            return Err("invalid slug");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Slug(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct SlugRef(str) where validate = validate_slug, error = &'static str, unsafe concat_closed;
+    }
+
+    #[test]
+    fn formats_and_validates() {
+        let n = 7;
+        assert_eq!("post-7", new_type_format!(Slug, "post-{}", n).unwrap());
+        assert_eq!(Err("invalid slug"), new_type_format!(Slug, "Post-{}", n));
+        assert_eq!("post-07", new_type_format!(Slug, "post-{n:02}", n = n).unwrap());
+    }
+
+    #[test]
+    fn concatenates_closed_values() {
+        let post = Slug::try_from("post").unwrap();
+        let suffix = SlugRef::try_as_ref("-7").unwrap();
+        let mut slug = post.clone() + suffix;
+        assert_eq!("post-7", slug);
+        slug += &post;
+        assert_eq!("post-7post", slug);
+        assert_eq!("postpost", post.clone() + &post);
+    }
+
+    proptest! {
+        #[test]
+        fn concatenations_are_valid(ref a in "[a-z0-9-]{0,6}", ref b in "[a-z0-9-]{0,6}") {
+            let joined = Slug::try_from(a.as_str()).unwrap() + SlugRef::try_as_ref(b).unwrap();
+            assert_eq!(Ok(joined), new_type_format!(Slug, "{}{}", a, b));
+        }
+    }
+}
@@ -0,0 +1,464 @@
+#[macro_export]
+/// Creates an owned new type without a matching reference type. The owned
+/// type itself must implement `NewTypeRef`, with `Owned` set to `Self`.
+///
+/// This generates the same validation, conversions, comparisons, and serde
+/// implementations that `new_type_pair!` generates between the owned type and
+/// the type it wraps. The wrapped value is viewed as a `str` unless another
+/// reference type is named with `as`, e.g. `pub struct Foo(Vec<u8> as [u8]);`.
+/// Values viewed as `[u8]` are serialized as bytes rather than as a sequence,
+/// which requires the wrapped type to convert from a `Vec<u8>`.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # extern crate arrayvec;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use arrayvec::ArrayString;
+/// use new_type_derive::NewTypeRef;
+///
+/// new_type_owned! {
+///     #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     /// A three letter currency code
+///     pub struct CurrencyCode(ArrayString<[u8; 3]>);
+/// }
+///
+/// impl NewTypeRef for CurrencyCode {
+///     type Owned = CurrencyCode;
+///     type InnerRef = str;
+///     type ValidationError = &'static str;
+///
+///     fn validate(value: &str) -> Result<(), Self::ValidationError> {
+///         if value.len() != 3 || !value.chars().all(|c| c.is_ascii_uppercase()) {
+///             return Err("currency codes are three uppercase letters");
+///         }
+///         Ok(())
+///     }
+///
+///     fn to_owned(&self) -> Self::Owned {
+///         *self
+///     }
+/// }
+///
+/// # pub fn main() {
+/// let code = CurrencyCode::try_from(ArrayString::from("EUR").unwrap()).unwrap();
+/// assert_eq!("EUR", code);
+/// assert!(CurrencyCode::try_from(ArrayString::from("eur").unwrap()).is_err());
+/// # }
+/// ```
+macro_rules! new_type_owned {
+    (@owned [$(#[$ometa:meta])*] $otype:ident [$itype:ty] [$stype:ty] $serde:tt) => {
+$(#[$ometa])*
+pub struct $otype {
+    inner: $itype
+}
+
+impl $otype {
+    /// Creates a new type by consuming and validating `value` and then returning the wrapped value or an error
+    pub fn try_from(value: impl Into<$itype>) -> Result<Self, <$otype as $crate::NewTypeRef>::ValidationError> {
+        let inner = value.into();
+        <$otype as $crate::NewTypeRef>::validate(inner.as_ref())?;
+        Ok($otype { inner })
+    }
+}
+
+impl ::std::borrow::Borrow<$stype> for $otype {
+    #[inline]
+    fn borrow(&self) -> &$stype {
+        self.as_ref()
+    }
+}
+
+impl ::std::convert::AsRef<$stype> for $otype {
+    #[inline]
+    fn as_ref(&self) -> &$stype {
+        self.inner.as_ref()
+    }
+}
+
+impl ::std::convert::AsRef<$otype> for $otype {
+    #[inline]
+    fn as_ref(&self) -> &$otype {
+        self
+    }
+}
+
+impl ::std::cmp::PartialEq<$otype> for $stype {
+    #[inline]
+    fn eq(&self, rhs: &$otype) -> bool {
+        self == AsRef::<$stype>::as_ref(rhs)
+    }
+}
+
+impl<'a> ::std::cmp::PartialEq<$otype> for &'a $stype {
+    #[inline]
+    fn eq(&self, rhs: &$otype) -> bool {
+        *self == AsRef::<$stype>::as_ref(rhs)
+    }
+}
+
+impl ::std::cmp::PartialEq<$stype> for $otype {
+    #[inline]
+    fn eq(&self, rhs: &$stype) -> bool {
+        AsRef::<$stype>::as_ref(self) == rhs
+    }
+}
+
+impl<'a> ::std::cmp::PartialEq<&'a $stype> for $otype {
+    #[inline]
+    fn eq(&self, rhs: &&'a $stype) -> bool {
+        AsRef::<$stype>::as_ref(self) == *rhs
+    }
+}
+
+impl ::std::cmp::PartialOrd<$otype> for $stype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$otype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(self, AsRef::<$stype>::as_ref(rhs))
+    }
+}
+
+impl<'a> ::std::cmp::PartialOrd<$otype> for &'a $stype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$otype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(*self, AsRef::<$stype>::as_ref(rhs))
+    }
+}
+
+impl ::std::cmp::PartialOrd<$stype> for $otype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$stype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(AsRef::<$stype>::as_ref(self), rhs)
+    }
+}
+
+impl<'a> ::std::cmp::PartialOrd<&'a $stype> for $otype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &&'a $stype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(AsRef::<$stype>::as_ref(self), *rhs)
+    }
+}
+
+impl From<$otype> for $itype {
+    #[inline]
+    fn from(o: $otype) -> Self {
+        o.inner
+    }
+}
+
+$crate::new_type_owned!(@serialize $serde $otype [$stype]);
+$crate::new_type_owned!(@deserialize $serde $otype [$itype]);
+    };
+
+    // Serializes types viewed as `[u8]` as bytes, since the `Serialize` of
+    // slices writes a sequence, and other types as the type they are viewed
+    // as.
+    (@serialize [bytes] $otype:ident [$stype:ty]) => {
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for $otype {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: ::serde::Serializer {
+        ::serde::Serializer::serialize_bytes(serializer, AsRef::<[u8]>::as_ref(self))
+    }
+}
+    };
+    (@serialize $serde:tt $otype:ident [$stype:ty]) => {
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for $otype {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: ::serde::Serializer {
+        ::serde::Serialize::serialize(AsRef::<$stype>::as_ref(self), serializer)
+    }
+}
+    };
+
+    // Deserializes types viewed as `str` by validating the string lent or
+    // handed over by the deserializer before building the wrapped value from
+    // it, and other types by validating the deserialized wrapped value.
+    (@deserialize [str] $otype:ident [$itype:ty]) => {
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for $otype {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where
+        D: ::serde::Deserializer<'de> {
+        struct Visitor;
+
+        impl<'de> ::serde::de::Visitor<'de> for Visitor {
+            type Value = $otype;
+
+            fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.write_str(concat!("a valid ", stringify!($otype)))
+            }
+
+            fn visit_str<E: ::serde::de::Error>(self, value: &str) -> ::std::result::Result<$otype, E> {
+                <$otype as $crate::NewTypeRef>::validate(value).map_err(|e| E::custom(e.to_string()))?;
+                let deserializer = ::serde::de::IntoDeserializer::<E>::into_deserializer(value);
+                let inner: $itype = ::serde::Deserialize::deserialize(deserializer)?;
+                Ok($otype { inner })
+            }
+
+            fn visit_string<E: ::serde::de::Error>(self, value: String) -> ::std::result::Result<$otype, E> {
+                <$otype as $crate::NewTypeRef>::validate(&value).map_err(|e| E::custom(e.to_string()))?;
+                let deserializer = ::serde::de::IntoDeserializer::<E>::into_deserializer(value);
+                let inner: $itype = ::serde::Deserialize::deserialize(deserializer)?;
+                Ok($otype { inner })
+            }
+
+            fn visit_bytes<E: ::serde::de::Error>(self, value: &[u8]) -> ::std::result::Result<$otype, E> {
+                match ::std::str::from_utf8(value) {
+                    Ok(value) => self.visit_str(value),
+                    Err(_) => Err(E::invalid_value(::serde::de::Unexpected::Bytes(value), &self)),
+                }
+            }
+
+            fn visit_byte_buf<E: ::serde::de::Error>(self, value: Vec<u8>) -> ::std::result::Result<$otype, E> {
+                match String::from_utf8(value) {
+                    Ok(value) => self.visit_string(value),
+                    Err(e) => Err(E::invalid_value(::serde::de::Unexpected::Bytes(&e.into_bytes()), &self)),
+                }
+            }
+        }
+
+        ::serde::Deserializer::deserialize_str(deserializer, Visitor)
+    }
+}
+    };
+    (@deserialize [bytes] $otype:ident [$itype:ty]) => {
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for $otype {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where
+        D: ::serde::Deserializer<'de> {
+        // Formats without a bytes type, such as JSON, hand over a sequence.
+        struct Visitor;
+
+        impl<'de> ::serde::de::Visitor<'de> for Visitor {
+            type Value = $otype;
+
+            fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.write_str(concat!("a valid ", stringify!($otype)))
+            }
+
+            fn visit_bytes<E: ::serde::de::Error>(self, value: &[u8]) -> ::std::result::Result<$otype, E> {
+                <$otype as $crate::NewTypeRef>::validate(value).map_err(|e| E::custom(e.to_string()))?;
+                Ok($otype { inner: From::from(value.to_vec()) })
+            }
+
+            fn visit_byte_buf<E: ::serde::de::Error>(self, value: Vec<u8>) -> ::std::result::Result<$otype, E> {
+                <$otype as $crate::NewTypeRef>::validate(&value).map_err(|e| E::custom(e.to_string()))?;
+                Ok($otype { inner: From::from(value) })
+            }
+
+            fn visit_seq<A: ::serde::de::SeqAccess<'de>>(self, mut seq: A) -> ::std::result::Result<$otype, A::Error> {
+                let mut value = Vec::with_capacity(::std::cmp::min(seq.size_hint().unwrap_or(0), 4096));
+                while let Some(byte) = seq.next_element()? {
+                    value.push(byte);
+                }
+                self.visit_byte_buf(value)
+            }
+        }
+
+        ::serde::Deserializer::deserialize_bytes(deserializer, Visitor)
+    }
+}
+    };
+    (@deserialize [] $otype:ident [$itype:ty]) => {
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for $otype {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where
+        D: ::serde::Deserializer<'de> {
+        let inner: $itype = ::serde::Deserialize::deserialize(deserializer)?;
+        $otype::try_from(inner).map_err(|e| ::serde::de::Error::custom(e.to_string()))
+    }
+}
+    };
+
+    (@munch) => {};
+    (@munch
+        $(#[$ometa:meta])*
+        pub struct $otype:ident($itype:ty as str);
+        $($rest:tt)*
+    ) => {
+        $crate::new_type_owned!(@owned [$(#[$ometa])*] $otype [$itype] [str] [str]);
+        $crate::new_type_owned!(@munch $($rest)*);
+    };
+    (@munch
+        $(#[$ometa:meta])*
+        pub struct $otype:ident($itype:ty as [u8]);
+        $($rest:tt)*
+    ) => {
+        $crate::new_type_owned!(@owned [$(#[$ometa])*] $otype [$itype] [[u8]] [bytes]);
+        $crate::new_type_owned!(@munch $($rest)*);
+    };
+    (@munch
+        $(#[$ometa:meta])*
+        pub struct $otype:ident($itype:ty as $stype:ty);
+        $($rest:tt)*
+    ) => {
+        $crate::new_type_owned!(@owned [$(#[$ometa])*] $otype [$itype] [$stype] []);
+        $crate::new_type_owned!(@munch $($rest)*);
+    };
+    (@munch
+        $(#[$ometa:meta])*
+        pub struct $otype:ident($itype:ty);
+        $($rest:tt)*
+    ) => {
+        $crate::new_type_owned!(@owned [$(#[$ometa])*] $otype [$itype] [str] [str]);
+        $crate::new_type_owned!(@munch $($rest)*);
+    };
+
+    ($($decls:tt)+) => {
+        $crate::new_type_owned!(@munch $($decls)+);
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use arrayvec::ArrayString;
+    #[cfg(feature = "serde")]
+    use bincode;
+    use NewTypeRef;
+
+    new_type_owned! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        /// An owned-only new type
+        pub struct Owned(String);
+
+        #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        /// A sized owned-only new type
+        pub struct ArrOwned(ArrayString<[u8; 8]> as str);
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        /// An owned-only new type wrapping bytes
+        pub struct BytesOwned(Vec<u8> as [u8]);
+    }
+
+    impl NewTypeRef for Owned {
+        type Owned = Owned;
+        type InnerRef = str;
+        type ValidationError = &'static str;
+
+        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
+            if value.is_empty() {
+                return Err("Empty!");
+            }
+            Ok(())
+        }
+
+        fn to_owned(&self) -> Self::Owned {
+            self.clone()
+        }
+    }
+
+    impl NewTypeRef for ArrOwned {
+        type Owned = ArrOwned;
+        type InnerRef = str;
+        type ValidationError = &'static str;
+
+        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
+            if !value.chars().all(|c| c.is_ascii_digit()) {
+                return Err("Not a number!");
+            }
+            Ok(())
+        }
+
+        fn to_owned(&self) -> Self::Owned {
+            *self
+        }
+    }
+
+    impl NewTypeRef for BytesOwned {
+        type Owned = BytesOwned;
+        type InnerRef = [u8];
+        type ValidationError = &'static str;
+
+        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
+            if value.contains(&0) {
+                return Err("Nul!");
+            }
+            Ok(())
+        }
+
+        fn to_owned(&self) -> Self::Owned {
+            self.clone()
+        }
+    }
+
+    const TEST_STRING: &str = "TESTING";
+
+    #[test]
+    fn validates() {
+        assert_eq!(Err("Empty!"), Owned::try_from(""));
+        assert_eq!(TEST_STRING, Owned::try_from(TEST_STRING).unwrap());
+        assert_eq!(
+            Err("Not a number!"),
+            ArrOwned::try_from(ArrayString::from("12a").unwrap())
+        );
+        assert_eq!("123", ArrOwned::try_from(ArrayString::from("123").unwrap()).unwrap());
+        assert_eq!(Err("Nul!"), BytesOwned::try_from(vec![1, 0, 2]));
+        assert_eq!(&[1u8, 2][..], BytesOwned::try_from(vec![1, 2]).unwrap());
+    }
+
+    #[test]
+    fn compares_with_inner() {
+        use std::cmp::Ordering;
+        let owned = Owned::try_from(TEST_STRING).unwrap();
+        assert_eq!(owned, TEST_STRING);
+        assert_eq!(owned, *TEST_STRING);
+        assert_eq!(TEST_STRING, owned);
+        assert_eq!(*TEST_STRING, owned);
+        assert_eq!(Some(Ordering::Less), owned.partial_cmp("U"));
+        assert_eq!(Some(Ordering::Greater), "U".partial_cmp(&owned));
+    }
+
+    #[test]
+    fn into_inner_roundtrip() {
+        let start = String::from(TEST_STRING);
+        let owned = Owned::try_from(start.clone()).unwrap();
+        let inner: &str = owned.as_ref();
+        assert_eq!(TEST_STRING, inner);
+        let end: String = owned.into();
+        assert_eq!(start, end);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn owned_roundtrips() {
+        let owned = ArrOwned::try_from(ArrayString::from("42").unwrap()).unwrap();
+        let serialized = bincode::serialize(&owned).expect("serialization to succeed");
+        assert_eq!(bincode::serialize("42").unwrap(), serialized);
+        let value: ArrOwned =
+            bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(owned, value);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn bytes_roundtrip() {
+        let owned = BytesOwned::try_from(vec![1, 2, 3]).unwrap();
+        let serialized = bincode::serialize(&owned).expect("serialization to succeed");
+        assert_eq!(bincode::serialize(&[1u8, 2, 3][..]).unwrap(), serialized);
+        let value: BytesOwned =
+            bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(owned, value);
+        let invalid = bincode::serialize(&[1u8, 0][..]).unwrap();
+        assert!(bincode::deserialize::<BytesOwned>(&invalid).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn bytes_are_serialized_as_bytes() {
+        use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_tokens, Token};
+
+        let owned = BytesOwned::try_from(vec![1, 2, 3]).unwrap();
+        assert_tokens(&owned, &[Token::Bytes(&[1, 2, 3])]);
+        assert_de_tokens(&owned, &[Token::ByteBuf(&[1, 2, 3])]);
+        assert_de_tokens(
+            &owned,
+            &[Token::Seq { len: Some(3) }, Token::U8(1), Token::U8(2), Token::U8(3), Token::SeqEnd],
+        );
+        assert_de_tokens_error::<BytesOwned>(&[Token::Bytes(&[1, 0])], "Nul!");
+        assert_de_tokens_error::<BytesOwned>(&[Token::ByteBuf(&[1, 0])], "Nul!");
+    }
+}
@@ -5,7 +5,8 @@
 /// In order to add additional implementation for both types, add an `impl`
 /// block for the reference type after the macro invocation.
 ///
-/// This macro can currently only build new types on `str` string slices.
+/// This macro builds new types on `str` string slices, or on the reference
+/// types of other pairs, as described under "Layered pairs" below.
 ///
 /// # Example
 ///
@@ -66,198 +67,1793 @@
 /// assert_eq!(1, MyNewType::try_from("X").unwrap().as_ref().len(),);
 /// # }
 /// ```
+///
+/// # Serialization
+///
+/// With the `serde` feature, both types implement `Serialize` and
+/// `Deserialize`. The owned type validates the string lent by the
+/// deserializer before copying it, so invalid values are rejected without
+/// allocating for them, and moves a `String` handed over by the deserializer
+/// into the wrapped value. Fields holding values that were validated before
+/// they were stored can skip validating them again with
+/// `#[serde(with = "new_type_derive::trusted_unchecked")]`.
+///
+/// # Declaring several pairs at once
+///
+/// Any number of pairs may be declared in a single invocation. A pair may
+/// name its validator and error type in a `where` clause after the reference
+/// type, in which case `NewTypeRef` is implemented for it. Such pairs convert
+/// the reference to the owned type with `From<&InnerRef>`, so wrappers with
+/// other inner types still need a hand-written `NewTypeRef` implementation.
+///
+/// If the invocation starts with an `enum` declaration, that enum becomes the
+/// `ValidationError` of every pair in the group, with one variant per owned
+/// type holding the error returned by that pair's validator. Every pair in
+/// such a group must declare both `validate` and `error`. The enum implements
+/// `Display` by delegating to the wrapped error.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// fn validate_user_name(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() {
+///         return Err("user names must not be empty");
+///     }
+///     Ok(())
+/// }
+///
+/// fn validate_team_name(value: &str) -> Result<(), String> {
+///     if !value.chars().all(char::is_alphanumeric) {
+///         return Err(format!("invalid team name: {}", value));
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, PartialEq, Eq)]
+///     /// Errors for every identifier in this module
+///     pub enum IdentifierError;
+///
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     /// The name of a user
+///     pub struct UserName(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     /// A reference to the name of a user
+///     pub struct UserNameRef(str)
+///         where validate = validate_user_name, error = &'static str;
+///
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     /// The name of a team
+///     pub struct TeamName(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     /// A reference to the name of a team
+///     pub struct TeamNameRef(str)
+///         where validate = validate_team_name, error = String;
+/// }
+///
+/// # pub fn main() {
+/// assert_eq!(
+///     Err(IdentifierError::UserName("user names must not be empty")),
+///     UserName::try_from(""),
+/// );
+/// assert_eq!(
+///     "invalid team name: a-team",
+///     TeamNameRef::try_as_ref("a-team").unwrap_err().to_string(),
+/// );
+/// assert_eq!("core", TeamName::try_from("core").unwrap());
+/// # }
+/// ```
+///
+/// # Shared values
+///
+/// The `shared` option declares a third type holding an `Arc` of the
+/// reference type, which is cheap to clone and to send across threads.
+/// Attributes for the shared type are written before its name. The shared
+/// type dereferences to the reference type, converts to and from the owned
+/// and reference types, and compares with all of them.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// fn validate_tenant(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() {
+///         return Err("tenants must not be empty");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Tenant(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct TenantRef(str)
+///     where
+///         validate = validate_tenant,
+///         error = &'static str,
+///         shared = #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)] SharedTenant;
+/// }
+///
+/// # pub fn main() {
+/// let tenant = SharedTenant::from(Tenant::try_from("acme").unwrap());
+/// let clone = tenant.clone();
+/// let reference: &TenantRef = &clone;
+/// assert_eq!(tenant, reference);
+/// assert_eq!("acme", tenant);
+/// # }
+/// ```
+///
+/// # Comparing values
+///
+/// The `compare` option names a type implementing `Comparison`, such as
+/// `AsciiCaseInsensitive`, `CaseInsensitive`, or `NaturalOrder`, which is
+/// then used by every comparison between the owned, reference, and wrapped
+/// types, including ordering them with `PartialOrd` and `Ord`. The macro
+/// implements `PartialEq`, `Eq`, `Hash`, `PartialOrd`, and `Ord` for the owned
+/// and reference types itself, so they must not be derived. Since the wrapped
+/// type would hash differently, `Borrow` of the wrapped type is not
+/// implemented. The reference type gains a `canonical` method returning the
+/// form shared by all equal values.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::AsciiCaseInsensitive;
+/// use std::collections::HashSet;
+///
+/// fn validate_header(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() || !value.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+///         return Err("invalid header name");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug)]
+///     pub struct HeaderName(String);
+///
+///     #[derive(Debug)]
+///     pub struct HeaderNameRef(str)
+///     where
+///         validate = validate_header,
+///         error = &'static str,
+///         compare = AsciiCaseInsensitive;
+/// }
+///
+/// # pub fn main() {
+/// let mut headers = HashSet::new();
+/// headers.insert(HeaderName::try_from("Content-Type").unwrap());
+/// let lookup = HeaderNameRef::try_as_ref("content-type").unwrap();
+/// assert!(headers.contains(lookup));
+/// assert_eq!("CONTENT-TYPE", lookup);
+/// assert_eq!("content-type", lookup.canonical());
+/// # }
+/// ```
+///
+/// # Secret values
+///
+/// The `secret` option is meant for values such as passwords and API keys.
+/// The macro implements `Debug` and `Display` for the owned and reference
+/// types without revealing the value, so they must not be derived, and
+/// compares them for equality in constant time. Secret values are not
+/// ordered, and the wrapped value is only reachable through `expose_secret`.
+/// The owned type is cleared when dropped, which requires the wrapped owned
+/// type to implement `Zeroize`, as `String` and `Box<str>` do. Secret values are not serialized
+/// unless declared with `secret = serialize`, but are always deserialized,
+/// so that they can be read from configuration files and requests. Strings
+/// and bytes handed over by the deserializer are copied into the new value
+/// and then cleared, whether or not they were valid. They may
+/// not declare `compare` or `shared`. Their reference types have none of the
+/// slicing methods, `find_iter`, or `masked`, which would reveal parts of the
+/// value, and do not implement `AsRef` of the wrapped type, so they cannot
+/// be used with `new_type_comparable!` or `new_type_refines!`, which compare
+/// and convert the plain values.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// fn validate_api_key(value: &str) -> Result<(), &'static str> {
+///     if value.len() < 8 {
+///         return Err("API keys have at least eight characters");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone)]
+///     pub struct ApiKey(String);
+///
+///     pub struct ApiKeyRef(str)
+///     where
+///         validate = validate_api_key,
+///         error = &'static str,
+///         secret;
+/// }
+///
+/// # pub fn main() {
+/// let key = ApiKey::try_from("0123456789abcdef").unwrap();
+/// assert_eq!("ApiKey(<redacted>)", format!("{:?}", key));
+/// assert_eq!(key, "0123456789abcdef");
+/// assert_eq!("0123456789abcdef", key.expose_secret());
+/// # }
+/// ```
+///
+/// Slicing or masking a secret reference does not compile:
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// # fn validate_api_key(value: &str) -> Result<(), &'static str> {
+/// #     Ok(())
+/// # }
+/// #
+/// # new_type_pair! {
+/// #     #[derive(Clone)]
+/// #     pub struct ApiKey(String);
+/// #
+/// #     pub struct ApiKeyRef(str) where validate = validate_api_key, error = &'static str, secret;
+/// # }
+/// #
+/// # pub fn main() {
+/// let key = ApiKey::try_from("0123456789abcdef").unwrap();
+/// let _ = key.strip_prefix("0123");
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// # fn validate_api_key(value: &str) -> Result<(), &'static str> {
+/// #     Ok(())
+/// # }
+/// #
+/// # new_type_pair! {
+/// #     #[derive(Clone)]
+/// #     pub struct ApiKey(String);
+/// #
+/// #     pub struct ApiKeyRef(str) where validate = validate_api_key, error = &'static str, secret;
+/// # }
+/// #
+/// # pub fn main() {
+/// let key = ApiKey::try_from("0123456789abcdef").unwrap();
+/// let _ = key.masked().to_string();
+/// # }
+/// ```
+///
+/// # Masked values
+///
+/// Every reference type that is not secret has a `masked` method returning a `Masked` adapter,
+/// which displays the value masked as chosen by `NewTypeRef::MASK`. Pairs
+/// declaring `validate` may set it with the `mask` option. With
+/// `debug = masked`, the macro implements `Debug` for the owned and reference
+/// types through the masked form, so it must not be derived.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::Mask;
+///
+/// fn validate_account(value: &str) -> Result<(), &'static str> {
+///     if value.len() < 6 {
+///         return Err("account ids have at least six characters");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct AccountId(String);
+///
+///     #[derive(Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct AccountIdRef(str)
+///     where
+///         validate = validate_account,
+///         error = &'static str,
+///         mask = Mask::Keep { first: 0, last: 4 },
+///         debug = masked;
+/// }
+///
+/// # pub fn main() {
+/// let account = AccountId::try_from("AC-0042-1234").unwrap();
+/// assert_eq!("********1234", account.masked().to_string());
+/// assert_eq!("AccountId(********1234)", format!("{:?}", account));
+/// # }
+/// ```
+///
+/// # Layered pairs
+///
+/// A pair may wrap the owned and reference types of another pair instead of
+/// `String` and `str`, in which case its validator receives values already
+/// validated as the inner reference type. Such pairs convert to and from the
+/// types they wrap, implement `AsRef<str>`, and are created from a `str` with
+/// `TryFromStr::try_from_str`, which validates the value as each type in the
+/// stack in turn. This requires the error type to implement `From` for the
+/// error of the inner type. Layered pairs may not declare `shared`.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::TryFromStr;
+///
+/// fn validate_non_empty(value: &str) -> Result<(), String> {
+///     if value.is_empty() {
+///         return Err(String::from("value must not be empty"));
+///     }
+///     Ok(())
+/// }
+///
+/// fn validate_email(value: &NonEmptyStrRef) -> Result<(), String> {
+///     if !AsRef::<str>::as_ref(value).contains('@') {
+///         return Err(String::from("emails contain an @"));
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct NonEmptyStr(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct NonEmptyStrRef(str) where validate = validate_non_empty, error = String;
+///
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Email(NonEmptyStr);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct EmailRef(NonEmptyStrRef) where validate = validate_email, error = String;
+/// }
+///
+/// # pub fn main() {
+/// assert_eq!("value must not be empty", EmailRef::try_from_str("").unwrap_err());
+/// assert_eq!("emails contain an @", EmailRef::try_from_str("ada").unwrap_err());
+/// let email = Email::from(EmailRef::try_from_str("ada@example.com").unwrap());
+/// let non_empty: &NonEmptyStrRef = email.as_ref().as_ref();
+/// assert_eq!("ada@example.com", non_empty);
+/// assert_eq!(email, EmailRef::try_as_ref(non_empty).unwrap());
+/// # }
+/// ```
+///
+/// # Segmented values
+///
+/// With `segments` and `separator`, values are made of segments, such as the
+/// labels of `org.team.service`, which are each validated as the reference
+/// type named by `segments`. Segmented pairs are validated by their segments
+/// alone, so they cannot declare `validate` or `error`, and use the error of
+/// the segment type. The reference type gains `segments`, `parent`,
+/// `first_segment`, `last_segment`, `starts_with`, and `join`, where parents
+/// and segments are views into the value. Joining requires the wrapped owned
+/// type to convert from a `String`, and fails if the segment holds the
+/// separator and splitting it there leaves a piece that is not a valid
+/// segment. Secret pairs cannot declare `segments`, since these views would
+/// expose parts of the secret.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// fn validate_label(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() || !value.bytes().all(|b| b.is_ascii_lowercase()) {
+///         return Err("labels are lowercase words");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Label(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct LabelRef(str) where validate = validate_label, error = &'static str;
+///
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct ServiceName(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct ServiceNameRef(str) where segments = LabelRef, separator = '.';
+/// }
+///
+/// # pub fn main() {
+/// assert!(ServiceName::try_from("org..api").is_err());
+/// let service = ServiceName::try_from("org.team.api").unwrap();
+/// assert_eq!(3, service.segments().count());
+/// assert_eq!("api", service.last_segment());
+/// let team = service.parent().unwrap();
+/// assert_eq!("org.team", team);
+/// assert!(service.starts_with(team));
+/// assert_eq!("org.team.web", team.join(LabelRef::try_as_ref("web").unwrap()).unwrap());
+/// # }
+/// ```
+///
+/// # Slicing values
+///
+/// Reference types wrapping `str` have `get`, `split_at`, `strip_prefix`,
+/// `strip_suffix`, and `trim` methods like those of `str`, which validate
+/// each slice and return it as the reference type, or as a `str` if it is
+/// not valid. Pairs whose validator accepts every substring of a valid value
+/// may declare `unsafe substring_closed`, which implements `SubstringClosed`
+/// so that slices are not validated again. This is an unchecked promise:
+/// slices are only validated in builds with debug assertions, so a validator
+/// that rejects some substring lets invalid values be created.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// fn validate_digits(value: &str) -> Result<(), &'static str> {
+///     if !value.bytes().all(|b| b.is_ascii_digit()) {
+///         return Err("value must only contain digits");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Digits(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct DigitsRef(str) where validate = validate_digits, error = &'static str, unsafe substring_closed;
+/// }
+///
+/// # pub fn main() {
+/// let digits = DigitsRef::try_as_ref("0123456789").unwrap();
+/// let area: &DigitsRef = digits.get(..3).unwrap();
+/// assert_eq!("012", area);
+/// assert_eq!(Some(Ok("456789")), digits.strip_prefix("0123").map(|r| r.map(|d| d.as_ref())));
+/// # }
+/// ```
+///
+/// Declaring the option without `unsafe` is an error:
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate new_type_derive;
+/// # fn validate_digits(_: &str) -> Result<(), &'static str> { Ok(()) }
+/// new_type_pair! {
+///     pub struct Digits(String);
+///     pub struct DigitsRef(str) where validate = validate_digits, error = &'static str, substring_closed;
+/// }
+/// # pub fn main() {}
+/// ```
+///
+/// # Finding values
+///
+/// Reference types wrapping `str` have a `find_iter` method that returns
+/// every value in a text that is valid as the reference type, along with its
+/// byte range, without copying them. The candidates it tries are found by
+/// `NewTypeRef::next_token`, which pairs can replace with `tokenize`.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use std::ops::Range;
+///
+/// fn validate_mention(value: &str) -> Result<(), &'static str> {
+///     if !value.starts_with('@') || value.len() < 2 {
+///         return Err("mentions start with an @");
+///     }
+///     Ok(())
+/// }
+///
+/// fn find_mention(text: &str, from: usize) -> Option<Range<usize>> {
+///     let start = from + text[from..].find('@')?;
+///     let len = text[start + 1..].find(|c: char| !c.is_alphanumeric()).unwrap_or(text.len() - start - 1);
+///     Some(start..start + 1 + len)
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Mention(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct MentionRef(str) where validate = validate_mention, error = &'static str, tokenize = find_mention;
+/// }
+///
+/// # pub fn main() {
+/// let text = "thanks @ada and @grace!";
+/// let mentions: Vec<_> = MentionRef::find_iter(text).map(|(_, m)| m).collect();
+/// assert_eq!(vec!["@ada", "@grace"], mentions);
+/// # }
+/// ```
+///
+/// # Concatenating values
+///
+/// Pairs whose validator accepts every concatenation of valid values may
+/// declare `unsafe concat_closed`, which implements `Add` and `AddAssign` of
+/// the owned and reference types for the owned type. This requires the
+/// wrapped owned type to implement `AddAssign<&str>`, as `String` does. Like
+/// `unsafe substring_closed`, this is an unchecked promise: concatenations
+/// are only validated in builds with debug assertions. Secret pairs cannot
+/// declare it, since growing a `String` frees its old buffer without clearing
+/// it. Other values can be built with `new_type_format!`, which validates the
+/// formatted value.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Word(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct WordRef(str) where validate = validate_word, error = &'static str, unsafe concat_closed;
+/// }
+///
+/// fn validate_word(value: &str) -> Result<(), &'static str> {
+///     if !value.chars().all(char::is_alphabetic) {
+///         return Err("words only contain letters");
+///     }
+///     Ok(())
+/// }
+///
+/// # pub fn main() {
+/// let word = Word::try_from("key").unwrap() + WordRef::try_as_ref("board").unwrap();
+/// assert_eq!("keyboard", word);
+/// assert!(new_type_format!(Word, "{}-{}", "key", 2).is_err());
+/// assert_eq!("keyboard", new_type_format!(Word, "{}{}", "key", "board").unwrap());
+/// # }
+/// ```
+///
+/// Declaring the option without `unsafe` is an error:
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate new_type_derive;
+/// # fn validate_word(_: &str) -> Result<(), &'static str> { Ok(()) }
+/// new_type_pair! {
+///     pub struct Word(String);
+///     pub struct WordRef(str) where validate = validate_word, error = &'static str, concat_closed;
+/// }
+/// # pub fn main() {}
+/// ```
+///
+/// # Building values
+///
+/// Pairs may declare `builder = FooBuilder`, optionally with attributes before
+/// the name, to generate a builder that appends characters and strings to an
+/// owned `String`. The builder rejects any addition that leaves a prefix that
+/// can never become valid, as reported by `NewTypeRef::validate_prefix`. It
+/// leaves the value unchanged in that case. Pairs declared with `validate` can
+/// name that check with `validate_prefix`. `finish` then validates the whole
+/// value.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Code(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct CodeRef(str)
+///     where
+///         validate = validate_code,
+///         error = &'static str,
+///         validate_prefix = validate_code_prefix,
+///         builder = #[derive(Debug)] CodeBuilder;
+/// }
+///
+/// fn validate_code_prefix(value: &str) -> Result<(), &'static str> {
+///     if value.len() > 4 || !value.bytes().all(|b| b.is_ascii_digit()) {
+///         return Err("codes have four digits");
+///     }
+///     Ok(())
+/// }
+///
+/// fn validate_code(value: &str) -> Result<(), &'static str> {
+///     validate_code_prefix(value)?;
+///     if value.len() != 4 {
+///         return Err("codes have four digits");
+///     }
+///     Ok(())
+/// }
+///
+/// # pub fn main() {
+/// let mut builder = CodeBuilder::new();
+/// builder.push_str("12").unwrap();
+/// assert!(builder.push('x').is_err());
+/// assert_eq!("12", builder.as_str());
+/// builder.push_str("34").unwrap();
+/// assert_eq!("1234", builder.finish().unwrap());
+/// # }
+/// ```
 macro_rules! new_type_pair {
-    (   $(#[$ometa:meta])*
+    // Munches the pair declarations one at a time. The first token tree holds
+    // the shared error enum declaration, if any, and the second accumulates
+    // its variants.
+    (@group $error:tt $variants:tt) => {
+        $crate::new_type_pair!(@error $error $variants);
+    };
+    (@group $error:tt $variants:tt
+        $(#[$ometa:meta])*
+        pub struct $otype:ident($itype:ty);
+
+        $(#[$rmeta:meta])*
+        pub struct $rtype:ident(str)
+        $($rest:tt)*
+    ) => {
+        $crate::new_type_pair!(@options $error $variants
+            [[$(#[$ometa])*] $otype [$itype] [$(#[$rmeta])*] $rtype [str]]
+            [[] [] [] [] [] [] [] [] [] [] [] [] []]
+            $($rest)*
+        );
+    };
+    (@group $error:tt $variants:tt
+        $(#[$ometa:meta])*
         pub struct $otype:ident($itype:ty);
 
-        $(#[$rmeta:meta])*
-        pub struct $rtype:ident($stype:ty);
-    ) => {
-$(#[$ometa])*
-pub struct $otype {
-    inner: $itype
+        $(#[$rmeta:meta])*
+        pub struct $rtype:ident($stype:ty)
+        $($rest:tt)*
+    ) => {
+        $crate::new_type_pair!(@options $error $variants
+            [[$(#[$ometa])*] $otype [$itype] [$(#[$rmeta])*] $rtype [$stype]]
+            [[] [] [] [] [] [] [layered] [] [] [] [] [] []]
+            $($rest)*
+        );
+    };
+
+    // Parses the optional `where` clause following the reference type into a
+    // fixed-order list of options:
+    // `[validate error shared compare debug mask layer segments substring concat builder prefix tokens]`,
+    // where `compare` holds either a `Comparison` or the `secret` mode, `layer`
+    // records whether the pair is layered on another reference type, and
+    // `substring` and `concat` record whether values are closed under slicing
+    // and concatenation.
+    (@options $error:tt $variants:tt $decl:tt $opts:tt where $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl $opts $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl $opts $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$_v:tt $($o:tt)*] validate = $validate:path , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [[$validate] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$_v:tt $($o:tt)*] validate = $validate:path ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [[$validate] $($o)*] ; $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $_e:tt $($o:tt)*] error = $verr:ty , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v [$verr] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $_e:tt $($o:tt)*] error = $verr:ty ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v [$verr] $($o)*] ; $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $_s:tt $($o:tt)*] shared = $(#[$shmeta:meta])* $shtype:ident $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e [[$(#[$shmeta])*] $shtype] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt [] $($o:tt)*] compare = $cmp:path , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s [$cmp] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt [] $($o:tt)*] compare = $cmp:path ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s [$cmp] $($o)*] ; $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt [] $($o:tt)*] secret = serialize $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s [secret serialize] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt [] $($o:tt)*] secret $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s [secret] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $_d:tt $($o:tt)*] debug = masked $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c [masked] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $_m:tt $($o:tt)*] mask = $mask:expr , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d [$mask] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $_m:tt $($o:tt)*] mask = $mask:expr ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d [$mask] $($o)*] ; $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $_g:tt $($o:tt)*] segments = $seg:ident , separator = $sep:expr , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l [$seg $sep] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $_g:tt $($o:tt)*] segments = $seg:ident , separator = $sep:expr ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l [$seg $sep] $($o)*] ; $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $g:tt $_k:tt $($o:tt)*] unsafe substring_closed $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l $g [closed] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt substring_closed $($rest:tt)*) => {
+        compile_error!("slices of substring-closed pairs are not validated, so the option must be declared as `unsafe substring_closed`");
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $g:tt $k:tt $_n:tt $($o:tt)*] unsafe concat_closed $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l $g $k [concat] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt concat_closed $($rest:tt)*) => {
+        compile_error!("concatenations of concat-closed pairs are not validated, so the option must be declared as `unsafe concat_closed`");
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $g:tt $k:tt $n:tt $_b:tt $($o:tt)*] builder = $(#[$bmeta:meta])* $btype:ident $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l $g $k $n [[$(#[$bmeta])*] $btype] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $g:tt $k:tt $n:tt $b:tt $_p:tt $($o:tt)*] validate_prefix = $prefix:path , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l $g $k $n $b [$prefix] $($o)*] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $g:tt $k:tt $n:tt $b:tt $_p:tt $($o:tt)*] validate_prefix = $prefix:path ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l $g $k $n $b [$prefix] $($o)*] ; $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $g:tt $k:tt $n:tt $b:tt $p:tt $_t:tt] tokenize = $tokenize:path , $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l $g $k $n $b $p [$tokenize]] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt [$v:tt $e:tt $s:tt $c:tt $d:tt $m:tt $l:tt $g:tt $k:tt $n:tt $b:tt $p:tt $_t:tt] tokenize = $tokenize:path ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@options $error $variants $decl [$v $e $s $c $d $m $l $g $k $n $b $p [$tokenize]] ; $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt segments $($rest:tt)*) => {
+        compile_error!("`segments` must be followed by a `separator`, as in `segments = LabelRef, separator = '.'`");
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt compare $($rest:tt)*) => {
+        compile_error!("a pair may declare only one of `compare` and `secret`");
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt secret $($rest:tt)*) => {
+        compile_error!("a pair may declare only one of `compare` and `secret`");
+    };
+    (@options [] $variants:tt $decl:tt $opts:tt ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@pair [] $decl $opts);
+        $crate::new_type_pair!(@group [] $variants $($rest)*);
+    };
+    (@options $error:tt [$($variants:tt)*] [$ometa:tt $otype:ident $($decl:tt)*] [[$($validate:tt)+] [$($verr:tt)+] $($opts:tt)*] ; $($rest:tt)*) => {
+        $crate::new_type_pair!(@pair $error [$ometa $otype $($decl)*] [[$($validate)+] [$($verr)+] $($opts)*]);
+        $crate::new_type_pair!(@group $error [$($variants)* [$otype $($verr)+]] $($rest)*);
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt ; $($rest:tt)*) => {
+        compile_error!("every pair sharing an error enum must declare both `validate` and `error`");
+    };
+    (@options $error:tt $variants:tt $decl:tt $opts:tt $unexpected:tt $($rest:tt)*) => {
+        compile_error!(concat!("unexpected token in `new_type_pair!` options: ", stringify!($unexpected)));
+    };
+
+    // Declares the error enum shared by every pair in the group.
+    (@error [] $variants:tt) => {};
+    (@error [[$(#[$emeta:meta])*] $etype:ident] [$([$otype:ident $verr:ty])*]) => {
+$(#[$emeta])*
+pub enum $etype {
+    $(
+        #[doc = concat!("Validation of a `", stringify!($otype), "` failed")]
+        $otype($verr),
+    )*
+}
+
+impl ::std::fmt::Display for $etype {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            $( $etype::$otype(ref e) => ::std::fmt::Display::fmt(e, f), )*
+        }
+    }
+}
+    };
+
+    // Implements `NewTypeRef` when the pair declares its own validator.
+    (@validate $error:tt $decl:tt [[] [] $_s:tt $_c:tt $_d:tt [] $_l:tt [] [] $_n:tt $_b:tt [] []]) => {};
+    (@validate [] [$ometa:tt $otype:ident [$itype:ty] $rmeta:tt $rtype:ident [$stype:ty]] [[] [] $_s:tt $_c:tt $_d:tt $mask:tt $_l:tt [$seg:ident $sep:expr] $closed:tt $_n:tt $_b:tt [] $tokens:tt]) => {
+impl $crate::NewTypeRef for $rtype {
+    type Owned = $otype;
+    type InnerRef = $stype;
+    type ValidationError = <$seg as $crate::NewTypeRef>::ValidationError;
+
+    $crate::new_type_pair!(@mask $mask);
+
+    #[inline]
+    fn validate(value: &$stype) -> Result<(), Self::ValidationError> {
+        for segment in value.split($sep) {
+            <$seg as $crate::NewTypeRef>::validate(segment)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn validate_prefix(value: &$stype) -> Result<(), Self::ValidationError> {
+        match value.rfind($sep) {
+            Some(end) => {
+                <Self as $crate::NewTypeRef>::validate(&value[..end])?;
+                <$seg as $crate::NewTypeRef>::validate_prefix(&value[end + $sep.len_utf8()..])
+            }
+            None => <$seg as $crate::NewTypeRef>::validate_prefix(value),
+        }
+    }
+
+    $crate::new_type_pair!(@tokens $tokens);
+
+    #[inline]
+    fn to_owned(&self) -> $otype {
+        let inner = ::std::convert::From::from(&self.inner);
+        $otype { inner }
+    }
+}
+    };
+    (@validate $error:tt $decl:tt [$_v:tt $_e:tt $_s:tt $_c:tt $_d:tt $_m:tt $_l:tt [$($_g:tt)+] $($_o:tt)*]) => {
+        compile_error!("segmented pairs are validated by their segments and cannot declare `validate`, `error`, or `validate_prefix`");
+    };
+    (@validate $error:tt $decl:tt [[] [] $($_o:tt)*]) => {
+        compile_error!("`mask`, `unsafe substring_closed`, `validate_prefix`, and `tokenize` can only be declared along with `validate` and `error`, otherwise implement `NewTypeRef` by hand");
+    };
+    (@validate [] [$ometa:tt $otype:ident [$itype:ty] $rmeta:tt $rtype:ident [$stype:ty]] [[$validate:path] [$verr:ty] $_s:tt $_c:tt $_d:tt $mask:tt $_l:tt $_g:tt $closed:tt $_n:tt $_b:tt $prefix:tt $tokens:tt]) => {
+impl $crate::NewTypeRef for $rtype {
+    type Owned = $otype;
+    type InnerRef = $stype;
+    type ValidationError = $verr;
+
+    $crate::new_type_pair!(@mask $mask);
+
+    #[inline]
+    fn validate(value: &$stype) -> Result<(), $verr> {
+        $validate(value)
+    }
+
+    $crate::new_type_pair!(@prefix $prefix [$stype] []);
+    $crate::new_type_pair!(@tokens $tokens);
+
+    #[inline]
+    fn to_owned(&self) -> $otype {
+        let inner = ::std::convert::From::from(&self.inner);
+        $otype { inner }
+    }
+}
+    };
+    (@validate [$emeta:tt $etype:ident] [$ometa:tt $otype:ident [$itype:ty] $rmeta:tt $rtype:ident [$stype:ty]] [[$validate:path] [$verr:ty] $_s:tt $_c:tt $_d:tt $mask:tt $_l:tt $_g:tt $closed:tt $_n:tt $_b:tt $prefix:tt $tokens:tt]) => {
+impl $crate::NewTypeRef for $rtype {
+    type Owned = $otype;
+    type InnerRef = $stype;
+    type ValidationError = $etype;
+
+    $crate::new_type_pair!(@mask $mask);
+
+    #[inline]
+    fn validate(value: &$stype) -> Result<(), $etype> {
+        $validate(value).map_err($etype::$otype)
+    }
+
+    $crate::new_type_pair!(@prefix $prefix [$stype] [$etype $otype]);
+    $crate::new_type_pair!(@tokens $tokens);
+
+    #[inline]
+    fn to_owned(&self) -> $otype {
+        let inner = ::std::convert::From::from(&self.inner);
+        $otype { inner }
+    }
+}
+    };
+    (@validate $error:tt $decl:tt $opts:tt) => {
+        compile_error!("`validate` and `error` must be declared together");
+    };
+
+    // Overrides `NewTypeRef::MASK` when declared with `mask`.
+    (@mask []) => {};
+    (@mask [$mask:expr]) => {
+        const MASK: $crate::Mask = $mask;
+    };
+
+    // Overrides `NewTypeRef::validate_prefix` when declared with
+    // `validate_prefix`, wrapping its error like that of `validate`.
+    (@prefix [] [$stype:ty] $_err:tt) => {};
+    (@prefix [$prefix:path] [$stype:ty] []) => {
+    #[inline]
+    fn validate_prefix(value: &$stype) -> Result<(), Self::ValidationError> {
+        $prefix(value)
+    }
+    };
+    (@prefix [$prefix:path] [$stype:ty] [$etype:ident $otype:ident]) => {
+    #[inline]
+    fn validate_prefix(value: &$stype) -> Result<(), Self::ValidationError> {
+        $prefix(value).map_err($etype::$otype)
+    }
+    };
+
+    // Overrides `NewTypeRef::next_token` when declared with `tokenize`.
+    (@tokens []) => {};
+    (@tokens [$tokenize:path]) => {
+    #[inline]
+    fn next_token(text: &str, from: usize) -> Option<::std::ops::Range<usize>> {
+        $tokenize(text, from)
+    }
+    };
+
+    // Implements `SubstringClosed` when declared with `unsafe
+    // substring_closed`.
+    (@closed [] $rtype:ident) => {};
+    (@closed [closed] $rtype:ident) => {
+#[allow(unsafe_code)]
+unsafe impl $crate::SubstringClosed for $rtype {}
+    };
+
+    // Declares the reference type along with its conversions and comparisons
+    // to the type it wraps.
+    (@ref [$(#[$rmeta:meta])*] $rtype:ident [$stype:ty] $cmp:tt) => {
+$(#[$rmeta])*
+#[repr(transparent)]
+pub struct $rtype {
+    inner: $stype
+}
+
+impl $rtype {
+    /// Creates a reference by validating `value` and then returning a typed reference to the value or an error
+    #[allow(unsafe_code)]
+    pub fn try_as_ref<S: AsRef<$stype> + ?Sized>(value: &S) -> Result<&Self, <$rtype as $crate::NewTypeRef>::ValidationError> {
+        let inner_ref = value.as_ref();
+        <Self as $crate::NewTypeRef>::validate(inner_ref)?;
+        Ok(#[allow(unsafe_code)] unsafe { Self::from_unchecked(inner_ref) })
+    }
+
+    /// Validates each of `values` like `try_as_ref`, returning the results in the same order
+    pub fn try_as_ref_all<'a, S: AsRef<$stype> + ?Sized>(values: &[&'a S]) -> Vec<Result<&'a Self, <$rtype as $crate::NewTypeRef>::ValidationError>> {
+        let mut results = Vec::with_capacity(values.len());
+        results.extend(values.iter().map(|value| Self::try_as_ref(*value)));
+        results
+    }
+
+    #[inline]
+    #[allow(trivial_casts, unsafe_code)]
+    unsafe fn from_unchecked(s: &$stype) -> &$rtype {
+        &*(s as *const $stype as *const $rtype)
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe impl $crate::TransparentRef for $rtype {
+    #[inline]
+    unsafe fn from_inner_unchecked(value: &$stype) -> &$rtype {
+        $rtype::from_unchecked(value)
+    }
+}
+
+$crate::new_type_pair!(@as_inner $cmp $rtype [$stype]);
+
+impl ::std::convert::AsRef<$rtype> for $rtype {
+    #[inline]
+    fn as_ref(&self) -> &$rtype {
+        &self
+    }
+}
+
+$crate::new_type_pair!(@ref_cmp $cmp $rtype [$stype]);
+$crate::new_type_pair!(@serialize $cmp $rtype |r| &r.inner);
+
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> ::serde::Deserialize<'de> for &'a $rtype {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where
+        D: ::serde::Deserializer<'de> {
+        let inner: &$stype = ::serde::Deserialize::deserialize(deserializer)?;
+        $rtype::try_as_ref(inner).map_err(|e| ::serde::de::Error::custom(e.to_string()))
+    }
+}
+    };
+
+    // Declares the comparisons between the reference type and the type it
+    // wraps, either byte-wise or through the `Comparison` named by the
+    // `compare` option. `Borrow` is only implemented for the former, since
+    // the wrapped type hashes differently in the latter.
+    (@ref_cmp [] $rtype:ident [$stype:ty]) => {
+impl ::std::borrow::Borrow<$stype> for $rtype {
+    #[inline]
+    fn borrow(&self) -> &$stype {
+        self.as_ref()
+    }
+}
+
+impl ::std::cmp::PartialEq<$rtype> for $stype {
+    #[inline]
+    fn eq(&self, rhs: &$rtype) -> bool {
+        self == &rhs.inner
+    }
+}
+
+impl<'a> ::std::cmp::PartialEq<&'a $rtype> for $stype {
+    #[inline]
+    fn eq(&self, rhs: &&'a $rtype) -> bool {
+        self == &rhs.inner
+    }
+}
+
+impl<'a> ::std::cmp::PartialEq<$rtype> for &'a $stype {
+    #[inline]
+    fn eq(&self, rhs: &$rtype) -> bool {
+        *self == &rhs.inner
+    }
+}
+
+impl ::std::cmp::PartialEq<$stype> for $rtype {
+    #[inline]
+    fn eq(&self, rhs: &$stype) -> bool {
+        &self.inner == rhs
+    }
+}
+
+impl<'a> ::std::cmp::PartialEq<&'a $stype> for $rtype {
+    #[inline]
+    fn eq(&self, rhs: &&'a $stype) -> bool {
+        &self.inner == *rhs
+    }
+}
+
+impl<'a> ::std::cmp::PartialEq<$stype> for &'a $rtype {
+    #[inline]
+    fn eq(&self, rhs: &$stype) -> bool {
+        &self.inner == rhs
+    }
+}
+
+impl ::std::cmp::PartialOrd<$rtype> for $stype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$rtype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(self, &rhs.inner)
+    }
+}
+
+impl<'a> ::std::cmp::PartialOrd<&'a $rtype> for $stype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &&'a $rtype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(self, &rhs.inner)
+    }
+}
+
+impl<'a> ::std::cmp::PartialOrd<$rtype> for &'a $stype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$rtype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(*self, &rhs.inner)
+    }
+}
+
+impl ::std::cmp::PartialOrd<$stype> for $rtype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$stype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(&self.inner, rhs)
+    }
+}
+
+impl<'a> ::std::cmp::PartialOrd<&'a $stype> for $rtype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &&'a $stype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(&self.inner, *rhs)
+    }
+}
+
+impl<'a> ::std::cmp::PartialOrd<$stype> for &'a $rtype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$stype) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(&self.inner, rhs)
+    }
+}
+    };
+    (@ref_cmp [secret $($_x:tt)*] $rtype:ident [$stype:ty]) => {
+impl $rtype {
+    /// Returns the secret value
+    #[inline]
+    pub fn expose_secret(&self) -> &$stype {
+        &self.inner
+    }
+}
+
+impl ::std::cmp::PartialEq for $rtype {
+    #[inline]
+    fn eq(&self, rhs: &$rtype) -> bool {
+        $crate::constant_time_eq(&self.inner, &rhs.inner)
+    }
+}
+
+impl ::std::cmp::Eq for $rtype {}
+
+$crate::new_type_pair!(@secret_eq [] $stype, $rtype, [] [.inner]);
+$crate::new_type_pair!(@secret_eq ['a] $stype, &'a $rtype, [] [.inner]);
+$crate::new_type_pair!(@secret_eq ['a] &'a $stype, $rtype, [] [.inner]);
+$crate::new_type_pair!(@secret_eq [] $rtype, $stype, [.inner] []);
+$crate::new_type_pair!(@secret_eq ['a] $rtype, &'a $stype, [.inner] []);
+$crate::new_type_pair!(@secret_eq ['a] &'a $rtype, $stype, [.inner] []);
+$crate::new_type_pair!(@redacted $rtype);
+    };
+    (@ref_cmp [$cmp:path] $rtype:ident [$stype:ty]) => {
+impl $rtype {
+    /// Returns the canonical form of the value, which is shared by every value equal to it
+    #[inline]
+    pub fn canonical(&self) -> ::std::borrow::Cow<'_, str> {
+        <$cmp as $crate::Comparison>::canonical(&self.inner)
+    }
+}
+
+impl ::std::cmp::PartialEq for $rtype {
+    #[inline]
+    fn eq(&self, rhs: &$rtype) -> bool {
+        <$cmp as $crate::Comparison>::eq(&self.inner, &rhs.inner)
+    }
+}
+
+impl ::std::cmp::Eq for $rtype {}
+
+impl ::std::hash::Hash for $rtype {
+    #[inline]
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        <$cmp as $crate::Comparison>::hash(&self.inner, state)
+    }
 }
 
-impl $otype {
-    /// Creates a new type by consuming and validating `value` and then returning the wrapped value or an error
-    pub fn try_from(value: impl Into<$itype>) -> Result<Self, <$rtype as NewTypeRef>::ValidationError> {
-        let inner = value.into();
-        <$rtype as NewTypeRef>::validate(inner.as_ref())?;
-        Ok($otype { inner })
+impl ::std::cmp::PartialOrd for $rtype {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$rtype) -> Option<::std::cmp::Ordering> {
+        Some(::std::cmp::Ord::cmp(self, rhs))
     }
+}
 
+impl ::std::cmp::Ord for $rtype {
+    #[inline]
+    fn cmp(&self, rhs: &$rtype) -> ::std::cmp::Ordering {
+        <$cmp as $crate::Comparison>::cmp(&self.inner, &rhs.inner)
+    }
 }
 
-$(#[$rmeta])*
-pub struct $rtype {
-    inner: $stype
+$crate::new_type_pair!(@cmp_via $cmp, [] $stype, $rtype, [] [.inner]);
+$crate::new_type_pair!(@cmp_via $cmp, ['a] $stype, &'a $rtype, [] [.inner]);
+$crate::new_type_pair!(@cmp_via $cmp, ['a] &'a $stype, $rtype, [] [.inner]);
+$crate::new_type_pair!(@cmp_via $cmp, [] $rtype, $stype, [.inner] []);
+$crate::new_type_pair!(@cmp_via $cmp, ['a] $rtype, &'a $stype, [.inner] []);
+$crate::new_type_pair!(@cmp_via $cmp, ['a] &'a $rtype, $stype, [.inner] []);
+    };
+
+    // Compares two types for equality in constant time, after applying the
+    // given field accesses to each side.
+    (@secret_eq [$($lt:tt)*] $lhs:ty, $rhs:ty, [$($l:tt)*] [$($r:tt)*]) => {
+impl<$($lt)*> ::std::cmp::PartialEq<$rhs> for $lhs {
+    #[inline]
+    fn eq(&self, rhs: &$rhs) -> bool {
+        $crate::constant_time_eq(&self$($l)*, &rhs$($r)*)
+    }
+}
+    };
+
+    // Implements `Debug` and `Display` for secret values without revealing
+    // them.
+    (@redacted $t:ident) => {
+impl ::std::fmt::Debug for $t {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.write_str(concat!(stringify!($t), "(<redacted>)"))
+    }
+}
+
+impl ::std::fmt::Display for $t {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+    };
+
+    // Implements `Debug` through `masked` when declared with `debug = masked`.
+    (@debug $cmp:tt [] $t:ident) => {};
+    (@debug [secret $($_x:tt)*] [masked] $t:ident) => {
+        compile_error!("secret pairs are always redacted and cannot declare `debug = masked`");
+    };
+    (@debug $cmp:tt [masked] $t:ident) => {
+impl ::std::fmt::Debug for $t {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_tuple(stringify!($t)).field(&self.masked()).finish()
+    }
+}
+    };
+
+    // Implements `AsRef` of the wrapped type and `masked`, unless the value
+    // is secret.
+    (@as_inner [secret $($_x:tt)*] $rtype:ident [$stype:ty]) => {};
+    (@as_inner $cmp:tt $rtype:ident [$stype:ty]) => {
+impl ::std::convert::AsRef<$stype> for $rtype {
+    #[inline]
+    fn as_ref(&self) -> &$stype {
+        &self.inner
+    }
 }
 
 impl $rtype {
-    /// Creates a reference by validating `value` and then returning a typed reference to the value or an error
-    #[allow(unsafe_code)]
-    pub fn try_as_ref<S: AsRef<$stype> + ?Sized>(value: &S) -> Result<&Self, <$rtype as NewTypeRef>::ValidationError> {
-        let inner_ref = value.as_ref();
-        <Self as NewTypeRef>::validate(inner_ref)?;
-        Ok(#[allow(unsafe_code)] unsafe { Self::from_unchecked(inner_ref) })
+    /// Returns an adapter that displays the value masked as chosen by `NewTypeRef::MASK`
+    #[inline]
+    pub fn masked(&self) -> $crate::Masked<'_> {
+        $crate::Masked::new(::std::convert::AsRef::<str>::as_ref(&self.inner), <Self as $crate::NewTypeRef>::MASK)
+    }
+}
+    };
+
+    // Implements `Serialize`, which secret values only do when declared with
+    // `secret = serialize`.
+    (@serialize [secret] $t:ident |$x:ident| $value:expr) => {};
+    (@serialize $cmp:tt $t:ident |$x:ident| $value:expr) => {
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for $t {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: ::serde::Serializer {
+        let $x = self;
+        ::serde::Serialize::serialize($value, serializer)
     }
+}
+    };
 
+    // Compares two types through a `Comparison`, after applying the given
+    // field accesses to each side.
+    (@cmp_via $cmp:path, [$($lt:tt)*] $lhs:ty, $rhs:ty, [$($l:tt)*] [$($r:tt)*]) => {
+impl<$($lt)*> ::std::cmp::PartialEq<$rhs> for $lhs {
     #[inline]
-    #[allow(trivial_casts, unsafe_code)]
-    unsafe fn from_unchecked(s: &$stype) -> &$rtype {
-        &*(s as *const $stype as *const $rtype)
+    fn eq(&self, rhs: &$rhs) -> bool {
+        <$cmp as $crate::Comparison>::eq(&self$($l)*, &rhs$($r)*)
     }
 }
 
-impl ::std::ops::Deref for $otype {
-    type Target = $rtype;
+impl<$($lt)*> ::std::cmp::PartialOrd<$rhs> for $lhs {
+    #[inline]
+    fn partial_cmp(&self, rhs: &$rhs) -> Option<::std::cmp::Ordering> {
+        Some(<$cmp as $crate::Comparison>::cmp(&self$($l)*, &rhs$($r)*))
+    }
+}
+    };
+
+    // Implements `TryFromStr`, which layered pairs do by first validating the
+    // value as the reference type they wrap. Layered pairs can also be
+    // viewed as the `str` at the bottom of the stack.
+    (@layer [] $otype:ident $rtype:ident [$stype:ty] $_s:tt) => {
+impl $crate::TryFromStr for $rtype {
+    type Error = <$rtype as $crate::NewTypeRef>::ValidationError;
 
     #[inline]
-    fn deref(&self) -> &$rtype {
-        self.as_ref()
+    fn try_from_str(value: &str) -> Result<&Self, Self::Error> {
+        $rtype::try_as_ref(value)
     }
 }
+    };
+    (@layer [layered] $otype:ident $rtype:ident [$stype:ty] []) => {
+impl $crate::TryFromStr for $rtype {
+    type Error = <$rtype as $crate::NewTypeRef>::ValidationError;
 
-impl ::std::borrow::Borrow<$rtype> for $otype {
     #[inline]
-    fn borrow(&self) -> &$rtype {
-        self.as_ref()
+    fn try_from_str(value: &str) -> Result<&Self, Self::Error> {
+        match <$stype as $crate::TryFromStr>::try_from_str(value) {
+            Ok(inner) => $rtype::try_as_ref::<$stype>(inner),
+            Err(e) => Err(From::from(e)),
+        }
     }
 }
 
-impl ::std::borrow::Borrow<$stype> for $otype {
+impl ::std::convert::AsRef<str> for $rtype {
     #[inline]
-    fn borrow(&self) -> &$stype {
-        self.as_ref().as_ref()
+    fn as_ref(&self) -> &str {
+        ::std::convert::AsRef::<str>::as_ref(&self.inner)
     }
 }
+    };
+    (@layer [layered] $otype:ident $rtype:ident [$stype:ty] $_s:tt) => {
+        compile_error!("layered pairs cannot declare a shared type");
+    };
 
-impl ::std::borrow::Borrow<$stype> for $rtype {
+    // Adds slicing operations to reference types wrapping `str`, which
+    // revalidate each slice unless the validator is substring-closed. Secret
+    // values get none, since they would reveal slices of the value.
+    (@substrings [layered] $cmp:tt $closed:tt $rtype:ident) => {};
+    (@substrings [] [secret $($_x:tt)*] $closed:tt $rtype:ident) => {};
+    (@substrings [] $cmp:tt $closed:tt $rtype:ident) => {
+impl $rtype {
+    /// Returns every value in `text` that is valid as the new type, along with its byte range, trying the candidates found by `NewTypeRef::next_token`
+    pub fn find_iter(text: &str) -> impl Iterator<Item = (::std::ops::Range<usize>, &$rtype)> {
+        let mut from = 0;
+        ::std::iter::from_fn(move || {
+            while let Some(range) = <$rtype as $crate::NewTypeRef>::next_token(text, from) {
+                if range.end <= from {
+                    return None;
+                }
+                from = range.end;
+                if let Some(value) = text.get(range.clone()).and_then(|slice| $rtype::try_as_ref(slice).ok()) {
+                    return Some((range, value));
+                }
+            }
+            None
+        })
+    }
+
+    /// Returns the slice of the value at `index`, or `None` if it is out of bounds, not on character boundaries, or not valid
     #[inline]
-    fn borrow(&self) -> &$stype {
-        self.as_ref()
+    pub fn get<I: ::std::slice::SliceIndex<str, Output = str>>(&self, index: I) -> Option<&$rtype> {
+        self.inner.get(index).and_then(|slice| $rtype::revalidate(slice).ok())
+    }
+
+    /// Splits the value in two at the byte index `mid`, like `str::split_at`, returning each half that is valid as the new type or the `str` slice otherwise
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid` is not on a character boundary or is past the end of the value.
+    #[inline]
+    pub fn split_at(&self, mid: usize) -> (Result<&$rtype, &str>, Result<&$rtype, &str>) {
+        let (head, tail) = self.inner.split_at(mid);
+        ($rtype::revalidate(head), $rtype::revalidate(tail))
+    }
+
+    /// Returns the value without `prefix`, or `None` if it does not start with it
+    #[inline]
+    pub fn strip_prefix(&self, prefix: &str) -> Option<Result<&$rtype, &str>> {
+        self.inner.strip_prefix(prefix).map($rtype::revalidate)
+    }
+
+    /// Returns the value without `suffix`, or `None` if it does not end with it
+    #[inline]
+    pub fn strip_suffix(&self, suffix: &str) -> Option<Result<&$rtype, &str>> {
+        self.inner.strip_suffix(suffix).map($rtype::revalidate)
+    }
+
+    /// Returns the value without leading and trailing whitespace
+    #[inline]
+    pub fn trim(&self) -> Result<&$rtype, &str> {
+        $rtype::revalidate(self.inner.trim())
+    }
+
+    /// Returns the value without leading whitespace
+    #[inline]
+    pub fn trim_start(&self) -> Result<&$rtype, &str> {
+        $rtype::revalidate(self.inner.trim_start())
+    }
+
+    /// Returns the value without trailing whitespace
+    #[inline]
+    pub fn trim_end(&self) -> Result<&$rtype, &str> {
+        $rtype::revalidate(self.inner.trim_end())
+    }
+
+    $crate::new_type_pair!(@revalidate $closed $rtype);
+}
+    };
+    (@revalidate [] $rtype:ident) => {
+    #[inline]
+    fn revalidate(slice: &str) -> Result<&$rtype, &str> {
+        if <$rtype as $crate::NewTypeRef>::validate(slice).is_err() {
+            return Err(slice);
+        }
+        Ok(#[allow(unsafe_code)] unsafe { $rtype::from_unchecked(slice) })
+    }
+    };
+    (@revalidate [closed] $rtype:ident) => {
+    #[inline]
+    fn revalidate(slice: &str) -> Result<&$rtype, &str> {
+        debug_assert!(
+            <$rtype as $crate::NewTypeRef>::validate(slice).is_ok(),
+            concat!(stringify!($rtype), " declares that its validator is substring-closed, but rejected {:?}"),
+            slice
+        );
+        Ok(#[allow(unsafe_code)] unsafe { $rtype::from_unchecked(slice) })
+    }
+    };
+
+    // Implements `Add` and `AddAssign` for owned values when declared with
+    // `unsafe concat_closed`, which requires the wrapped owned type to
+    // implement `AddAssign<&str>`.
+    (@concat [] $cmp:tt $otype:ident $rtype:ident) => {};
+    (@concat [concat] [secret $($_x:tt)*] $otype:ident $rtype:ident) => {
+        compile_error!("secret pairs cannot declare `concat_closed`, since growing the value frees its old buffer without clearing it");
+    };
+    (@concat [concat] $cmp:tt $otype:ident $rtype:ident) => {
+impl<'a> ::std::ops::AddAssign<&'a $rtype> for $otype {
+    #[inline]
+    fn add_assign(&mut self, rhs: &'a $rtype) {
+        ::std::ops::AddAssign::add_assign(&mut self.inner, &rhs.inner);
+        debug_assert!(
+            <$rtype as $crate::NewTypeRef>::validate(::std::convert::AsRef::<$rtype>::as_ref(self).inner.as_ref()).is_ok(),
+            concat!(stringify!($rtype), " declares that it is closed under concatenation, but rejected a concatenation")
+        );
     }
 }
 
-impl ::std::convert::AsRef<$rtype> for $otype {
+impl<'a> ::std::ops::AddAssign<&'a $otype> for $otype {
     #[inline]
-    fn as_ref(&self) -> &$rtype {
-        #[allow(unsafe_code)] unsafe { $rtype::from_unchecked(self.inner.as_ref()) }
+    fn add_assign(&mut self, rhs: &'a $otype) {
+        ::std::ops::AddAssign::add_assign(self, ::std::convert::AsRef::<$rtype>::as_ref(rhs))
     }
 }
 
-impl ::std::convert::AsRef<$stype> for $rtype {
+impl<'a> ::std::ops::Add<&'a $rtype> for $otype {
+    type Output = $otype;
+
     #[inline]
-    fn as_ref(&self) -> &$stype {
+    fn add(mut self, rhs: &'a $rtype) -> $otype {
+        self += rhs;
+        self
+    }
+}
+
+impl<'a> ::std::ops::Add<&'a $otype> for $otype {
+    type Output = $otype;
+
+    #[inline]
+    fn add(mut self, rhs: &'a $otype) -> $otype {
+        self += rhs;
+        self
+    }
+}
+    };
+
+    // Declares the optional builder, which appends to a `String` while
+    // rejecting prefixes that can never become valid.
+    (@builder [] $otype:ident $rtype:ident $cmp:tt $layer:tt) => {};
+    (@builder $builder:tt $otype:ident $rtype:ident [secret $($_x:tt)*] $layer:tt) => {
+        compile_error!("secret pairs cannot declare a builder, since it would not be cleared when dropped");
+    };
+    (@builder $builder:tt $otype:ident $rtype:ident $cmp:tt [layered]) => {
+        compile_error!("layered pairs cannot declare a builder");
+    };
+    (@builder [[$(#[$bmeta:meta])*] $btype:ident] $otype:ident $rtype:ident $cmp:tt []) => {
+$(#[$bmeta])*
+pub struct $btype {
+    inner: String
+}
+
+impl $btype {
+    /// Creates an empty builder
+    #[inline]
+    pub fn new() -> Self {
+        $btype { inner: String::new() }
+    }
+
+    /// Appends `c`, unless the value would then start with a prefix that can never become valid, in which case the builder is left unchanged
+    pub fn push(&mut self, c: char) -> Result<(), <$rtype as $crate::NewTypeRef>::ValidationError> {
+        let len = self.inner.len();
+        self.inner.push(c);
+        self.check_prefix(len)
+    }
+
+    /// Appends `s`, unless the value would then start with a prefix that can never become valid, in which case the builder is left unchanged
+    pub fn push_str(&mut self, s: &str) -> Result<(), <$rtype as $crate::NewTypeRef>::ValidationError> {
+        let len = self.inner.len();
+        self.inner.push_str(s);
+        self.check_prefix(len)
+    }
+
+    /// Returns the value built so far, which may not be valid yet
+    #[inline]
+    pub fn as_str(&self) -> &str {
         &self.inner
     }
+
+    /// Validates the value built so far, returning it as the owned type or an error
+    pub fn finish(self) -> Result<$otype, <$rtype as $crate::NewTypeRef>::ValidationError> {
+        $otype::try_from(self.inner)
+    }
+
+    fn check_prefix(&mut self, len: usize) -> Result<(), <$rtype as $crate::NewTypeRef>::ValidationError> {
+        let checked = <$rtype as $crate::NewTypeRef>::validate_prefix(&self.inner);
+        if checked.is_err() {
+            self.inner.truncate(len);
+        }
+        checked
+    }
 }
 
-impl ::std::convert::AsRef<$rtype> for $rtype {
+impl Default for $btype {
+    #[inline]
+    fn default() -> Self {
+        $btype::new()
+    }
+}
+    };
+
+    // Adds accessors for the segments of segmented pairs. Every run of
+    // leading segments of a valid value is valid as well, since only the
+    // segments are validated.
+    (@segments [] $cmp:tt $otype:ident $rtype:ident) => {};
+    (@segments $segs:tt [secret $($_x:tt)*] $otype:ident $rtype:ident) => {
+        compile_error!("secret pairs cannot declare `segments`, since segments and parents would expose parts of the secret");
+    };
+    (@segments [$seg:ident $sep:expr] $cmp:tt $otype:ident $rtype:ident) => {
+impl $rtype {
+    /// Returns the segments of the value in order
+    #[inline]
+    pub fn segments(&self) -> impl Iterator<Item = &$seg> + '_ {
+        self.inner.split($sep).map(|segment| {
+            #[allow(unsafe_code)] unsafe { <$seg as $crate::TransparentRef>::from_inner_unchecked(segment) }
+        })
+    }
+
+    /// Returns the value without its last segment, or `None` if it has a single segment
+    #[inline]
+    pub fn parent(&self) -> Option<&$rtype> {
+        self.inner.rfind($sep).map(|end| {
+            #[allow(unsafe_code)] unsafe { $rtype::from_unchecked(&self.inner[..end]) }
+        })
+    }
+
+    /// Returns the first segment of the value
+    #[inline]
+    pub fn first_segment(&self) -> &$seg {
+        let end = self.inner.find($sep).unwrap_or(self.inner.len());
+        #[allow(unsafe_code)] unsafe { <$seg as $crate::TransparentRef>::from_inner_unchecked(&self.inner[..end]) }
+    }
+
+    /// Returns the last segment of the value, like the file name of a path
+    #[inline]
+    pub fn last_segment(&self) -> &$seg {
+        let start = self.inner.rfind($sep).map_or(0, |end| end + $sep.len_utf8());
+        #[allow(unsafe_code)] unsafe { <$seg as $crate::TransparentRef>::from_inner_unchecked(&self.inner[start..]) }
+    }
+
+    /// Returns whether the leading segments of the value are the segments of `prefix`
+    #[inline]
+    pub fn starts_with(&self, prefix: &$rtype) -> bool {
+        match self.inner.get(prefix.inner.len()..) {
+            Some(rest) => self.inner.starts_with(&prefix.inner) && (rest.is_empty() || rest.starts_with($sep)),
+            None => false,
+        }
+    }
+
+    /// Returns a new value made of the segments of the value followed by `segment`
+    ///
+    /// A segment that holds the separator is split there, and each of its
+    /// pieces must then be a valid segment.
+    pub fn join(&self, segment: &$seg) -> Result<$otype, <$seg as $crate::NewTypeRef>::ValidationError> {
+        let segment = ::std::convert::AsRef::<<$seg as $crate::NewTypeRef>::InnerRef>::as_ref(segment);
+        if segment.contains($sep) {
+            for piece in segment.split($sep) {
+                <$seg as $crate::NewTypeRef>::validate(piece)?;
+            }
+        }
+        let mut joined = String::with_capacity(self.inner.len() + $sep.len_utf8() + segment.len());
+        joined.push_str(&self.inner);
+        joined.push($sep);
+        joined.push_str(segment);
+        Ok($otype { inner: ::std::convert::From::from(joined) })
+    }
+}
+    };
+
+    // Implements the visitor methods for strings and bytes handed over by the
+    // deserializer. The wrapped type of a secret pair may copy or reallocate
+    // a `String` it is given, freeing the buffer without clearing it, so
+    // secrets are copied out of the buffer, which is then cleared.
+    (@visit_owned [secret $($_x:tt)*] $otype:ident $rtype:ident [$itype:ty]) => {
+    fn visit_string<E: ::serde::de::Error>(self, mut value: String) -> ::std::result::Result<$otype, E> {
+        let result = self.visit_str(&value);
+        $crate::zeroize(&mut value);
+        result
+    }
+
+    fn visit_byte_buf<E: ::serde::de::Error>(self, mut value: Vec<u8>) -> ::std::result::Result<$otype, E> {
+        let result = self.visit_bytes(&value);
+        $crate::zeroize(&mut value);
+        result
+    }
+    };
+    (@visit_owned $cmp:tt $otype:ident $rtype:ident [$itype:ty]) => {
+    fn visit_string<E: ::serde::de::Error>(self, value: String) -> ::std::result::Result<$otype, E> {
+        <$rtype as $crate::TryFromStr>::try_from_str(&value).map_err(E::custom)?;
+        let deserializer = ::serde::de::IntoDeserializer::<E>::into_deserializer(value);
+        let inner: $itype = ::serde::Deserialize::deserialize(deserializer)?;
+        Ok($otype { inner })
+    }
+
+    fn visit_byte_buf<E: ::serde::de::Error>(self, value: Vec<u8>) -> ::std::result::Result<$otype, E> {
+        match String::from_utf8(value) {
+            Ok(value) => self.visit_string(value),
+            Err(e) => Err(E::invalid_value(::serde::de::Unexpected::Bytes(&e.into_bytes()), &self)),
+        }
+    }
+    };
+
+    // Declares the optional shared type, an `Arc` around the reference type.
+    (@shared $otype:ident $rtype:ident [$stype:ty] [$_v:tt $_e:tt [] $($_o:tt)*]) => {};
+    (@shared $otype:ident $rtype:ident [$stype:ty] [$_v:tt $_e:tt $_s:tt [secret $($_x:tt)*] $($_o:tt)*]) => {
+        compile_error!("secret pairs cannot declare a shared type, since it would not be cleared when dropped");
+    };
+    (@shared $otype:ident $rtype:ident [$stype:ty] [$_v:tt $_e:tt [[$(#[$shmeta:meta])*] $shtype:ident] $cmp:tt $($_o:tt)*]) => {
+$(#[$shmeta])*
+pub struct $shtype {
+    inner: ::std::sync::Arc<$rtype>
+}
+
+impl $shtype {
+    /// Creates a shared new type by validating `value` and then copying it into a new shared allocation
+    pub fn try_from<S: AsRef<$stype> + ?Sized>(value: &S) -> Result<Self, <$rtype as $crate::NewTypeRef>::ValidationError> {
+        $rtype::try_as_ref(value).map(From::from)
+    }
+}
+
+impl ::std::ops::Deref for $shtype {
+    type Target = $rtype;
+
+    #[inline]
+    fn deref(&self) -> &$rtype {
+        &self.inner
+    }
+}
+
+impl ::std::borrow::Borrow<$rtype> for $shtype {
+    #[inline]
+    fn borrow(&self) -> &$rtype {
+        &self.inner
+    }
+}
+
+$crate::new_type_pair!(@borrow $cmp $shtype [$stype] |s| &s.inner.inner);
+
+impl ::std::convert::AsRef<$rtype> for $shtype {
     #[inline]
     fn as_ref(&self) -> &$rtype {
-        &self
+        &self.inner
     }
 }
 
-impl<'a> ::std::cmp::PartialEq<$otype> for &'a $rtype {
+impl ::std::convert::AsRef<$stype> for $shtype {
     #[inline]
-    fn eq(&self, rhs: &$otype) -> bool {
-        *self == rhs.as_ref()
+    fn as_ref(&self) -> &$stype {
+        &self.inner.inner
     }
 }
 
-impl ::std::cmp::PartialEq<$otype> for $rtype {
+impl<'a> From<&'a $rtype> for $shtype {
     #[inline]
-    fn eq(&self, rhs: &$otype) -> bool {
-        self == rhs.as_ref()
+    fn from(r: &'a $rtype) -> Self {
+        let inner: ::std::sync::Arc<$stype> = From::from(&r.inner);
+        #[allow(unsafe_code)]
+        let inner = unsafe { ::std::sync::Arc::from_raw(::std::sync::Arc::into_raw(inner) as *const $rtype) };
+        $shtype { inner }
     }
 }
 
-impl<'a> ::std::cmp::PartialEq<$otype> for &'a $stype {
+impl From<$otype> for $shtype {
     #[inline]
-    fn eq(&self, rhs: &$otype) -> bool {
-        self == rhs.as_ref()
+    fn from(o: $otype) -> Self {
+        From::from(AsRef::<$rtype>::as_ref(&o))
     }
 }
 
-impl ::std::cmp::PartialEq<$otype> for $stype {
+impl<'a> From<&'a $shtype> for $otype {
     #[inline]
-    fn eq(&self, rhs: &$otype) -> bool {
-        self == rhs.as_ref()
+    fn from(s: &'a $shtype) -> Self {
+        $crate::NewTypeRef::to_owned(&*s.inner)
     }
 }
 
-impl<'a> ::std::cmp::PartialEq<&'a $stype> for $otype {
+impl From<$shtype> for $otype {
     #[inline]
-    fn eq(&self, rhs: &&'a $stype) -> bool {
-        self.as_ref() == *rhs
+    fn from(s: $shtype) -> Self {
+        $crate::NewTypeRef::to_owned(&*s.inner)
     }
 }
 
-impl ::std::cmp::PartialEq<$stype> for $otype {
+$crate::new_type_pair!(@shared_cmp [] $shtype, $stype, [* *] [*]);
+$crate::new_type_pair!(@shared_cmp ['a] $shtype, &'a $stype, [* *] [* *]);
+$crate::new_type_pair!(@shared_cmp [] $shtype, $rtype, [* *] [*]);
+$crate::new_type_pair!(@shared_cmp ['a] $shtype, &'a $rtype, [* *] [* *]);
+$crate::new_type_pair!(@shared_cmp [] $shtype, $otype, [* *] [*]);
+$crate::new_type_pair!(@shared_cmp [] $stype, $shtype, [*] [* *]);
+$crate::new_type_pair!(@shared_cmp ['a] &'a $stype, $shtype, [* *] [* *]);
+$crate::new_type_pair!(@shared_cmp [] $rtype, $shtype, [*] [* *]);
+$crate::new_type_pair!(@shared_cmp ['a] &'a $rtype, $shtype, [* *] [* *]);
+$crate::new_type_pair!(@shared_cmp [] $otype, $shtype, [*] [* *]);
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for $shtype {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where S: ::serde::Serializer {
+        ::serde::Serializer::serialize_str(serializer, AsRef::<$stype>::as_ref(self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for $shtype {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where
+        D: ::serde::Deserializer<'de> {
+        let owned: $otype = ::serde::Deserialize::deserialize(deserializer)?;
+        Ok(From::from(owned))
+    }
+}
+    };
+
+    // Implements `Borrow` of the wrapped type unless values are compared
+    // through a `Comparison` or are secret.
+    (@borrow [] $t:ident [$stype:ty] |$x:ident| $borrow:expr) => {
+impl ::std::borrow::Borrow<$stype> for $t {
+    #[inline]
+    fn borrow(&self) -> &$stype {
+        let $x = self;
+        $borrow
+    }
+}
+    };
+    (@borrow $cmp:tt $t:ident [$stype:ty] |$x:ident| $borrow:expr) => {};
+
+    // Compares owned values through the reference type when values are
+    // compared through a `Comparison` or are secret, since derived
+    // implementations would compare them byte-wise. Secret values are not
+    // ordered, and are cleared when dropped.
+    (@owned_cmp [] $otype:ident $rtype:ident [$stype:ty]) => {
+$crate::new_type_pair!(@owned_ord $otype $rtype [$stype]);
+    };
+    (@owned_cmp [secret $($_x:tt)*] $otype:ident $rtype:ident [$stype:ty]) => {
+impl ::std::cmp::PartialEq for $otype {
     #[inline]
-    fn eq(&self, rhs: &$stype) -> bool {
-        self.as_ref() == *rhs
+    fn eq(&self, rhs: &$otype) -> bool {
+        AsRef::<$rtype>::as_ref(self) == AsRef::<$rtype>::as_ref(rhs)
     }
 }
 
-impl<'a> ::std::cmp::PartialEq<&'a $rtype> for $otype {
-    #[inline]
-    fn eq(&self, rhs: &&'a $rtype) -> bool {
-        self.as_ref() == *rhs
+impl ::std::cmp::Eq for $otype {}
+
+impl Drop for $otype {
+    fn drop(&mut self) {
+        $crate::zeroize(&mut self.inner)
     }
 }
 
-impl ::std::cmp::PartialEq<$rtype> for $otype {
+$crate::new_type_pair!(@redacted $otype);
+    };
+    (@owned_cmp [$cmp:path] $otype:ident $rtype:ident [$stype:ty]) => {
+impl ::std::cmp::PartialEq for $otype {
     #[inline]
-    fn eq(&self, rhs: &$rtype) -> bool {
-        self.as_ref() == rhs
+    fn eq(&self, rhs: &$otype) -> bool {
+        AsRef::<$rtype>::as_ref(self) == AsRef::<$rtype>::as_ref(rhs)
     }
 }
 
-impl ::std::cmp::PartialEq<$rtype> for $stype {
+impl ::std::cmp::Eq for $otype {}
+
+impl ::std::hash::Hash for $otype {
     #[inline]
-    fn eq(&self, rhs: &$rtype) -> bool {
-        self == &rhs.inner
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        ::std::hash::Hash::hash(AsRef::<$rtype>::as_ref(self), state)
     }
 }
 
-impl<'a> ::std::cmp::PartialEq<&'a $rtype> for $stype {
+impl ::std::cmp::PartialOrd for $otype {
     #[inline]
-    fn eq(&self, rhs: &&'a $rtype) -> bool {
-        self == &rhs.inner
+    fn partial_cmp(&self, rhs: &$otype) -> Option<::std::cmp::Ordering> {
+        Some(::std::cmp::Ord::cmp(self, rhs))
     }
 }
 
-impl<'a> ::std::cmp::PartialEq<$rtype> for &'a $stype {
+impl ::std::cmp::Ord for $otype {
     #[inline]
-    fn eq(&self, rhs: &$rtype) -> bool {
-        *self == &rhs.inner
+    fn cmp(&self, rhs: &$otype) -> ::std::cmp::Ordering {
+        ::std::cmp::Ord::cmp(AsRef::<$rtype>::as_ref(self), AsRef::<$rtype>::as_ref(rhs))
     }
 }
 
-impl ::std::cmp::PartialEq<$stype> for $rtype {
+$crate::new_type_pair!(@owned_ord $otype $rtype [$stype]);
+    };
+
+    // Unwraps owned values, unless they are secret and must be cleared when
+    // dropped.
+    (@into_inner [secret $($_x:tt)*] $otype:ident [$itype:ty]) => {};
+    (@into_inner $cmp:tt $otype:ident [$itype:ty]) => {
+impl From<$otype> for $itype {
     #[inline]
-    fn eq(&self, rhs: &$stype) -> bool {
-        &self.inner == rhs
+    fn from(o: $otype) -> Self {
+        o.inner
     }
 }
 
-impl<'a> ::std::cmp::PartialEq<&'a $stype> for $rtype {
+impl $crate::TransparentOwned for $otype {
+    type Inner = $itype;
+
     #[inline]
-    fn eq(&self, rhs: &&'a $stype) -> bool {
-        &self.inner == *rhs
+    fn into_inner(self) -> $itype {
+        self.inner
     }
-}
 
-impl<'a> ::std::cmp::PartialEq<$stype> for &'a $rtype {
     #[inline]
-    fn eq(&self, rhs: &$stype) -> bool {
-        &self.inner == rhs
+    #[allow(unsafe_code)]
+    unsafe fn from_inner_unchecked(inner: $itype) -> $otype {
+        $otype { inner }
     }
 }
+    };
 
+    // Orders owned values against the reference type and the type that it
+    // wraps.
+    (@owned_ord $otype:ident $rtype:ident [$stype:ty]) => {
 impl<'a> ::std::cmp::PartialOrd<$otype> for &'a $rtype {
     #[inline]
     fn partial_cmp(&self, rhs: &$otype) -> Option<::std::cmp::Ordering> {
@@ -337,97 +1933,198 @@ impl ::std::cmp::PartialOrd<$stype> for $otype {
         )
     }
 }
+    };
 
-impl ::std::cmp::PartialOrd<$rtype> for $stype {
+    // Compares two types by dereferencing each side down to a type that
+    // `new_type_pair!` already compares.
+    (@shared_cmp [$($lt:tt)*] $lhs:ty, $rhs:ty, [$($l:tt)*] [$($r:tt)*]) => {
+impl<$($lt)*> ::std::cmp::PartialEq<$rhs> for $lhs {
     #[inline]
-    fn partial_cmp(&self, rhs: &$rtype) -> Option<::std::cmp::Ordering> {
-        ::std::cmp::PartialOrd::partial_cmp(self, &rhs.inner)
+    fn eq(&self, rhs: &$rhs) -> bool {
+        $($l)*self == $($r)*rhs
     }
 }
 
-impl<'a> ::std::cmp::PartialOrd<&'a $rtype> for $stype {
+impl<$($lt)*> ::std::cmp::PartialOrd<$rhs> for $lhs {
     #[inline]
-    fn partial_cmp(&self, rhs: &&'a $rtype) -> Option<::std::cmp::Ordering> {
-        ::std::cmp::PartialOrd::partial_cmp(self, &rhs.inner)
+    fn partial_cmp(&self, rhs: &$rhs) -> Option<::std::cmp::Ordering> {
+        ::std::cmp::PartialOrd::partial_cmp(&$($l)*self, &$($r)*rhs)
     }
 }
+    };
+
+    // Declares the owned type along with its conversions and comparisons to
+    // the reference type and the type that it wraps.
+    (@pair $error:tt
+        [[$(#[$ometa:meta])*] $otype:ident [$itype:ty] [$(#[$rmeta:meta])*] $rtype:ident [$stype:ty]]
+        [$v:tt $e:tt $s:tt $cmp:tt $dbg:tt $mask:tt $layer:tt $segs:tt $closed:tt $concat:tt $builder:tt $prefix:tt $tokens:tt]
+    ) => {
+$crate::new_type_pair!(@validate $error [[] $otype [$itype] [] $rtype [$stype]] [$v $e $s $cmp $dbg $mask $layer $segs $closed $concat $builder $prefix $tokens]);
+$crate::new_type_pair!(@ref [$(#[$rmeta])*] $rtype [$stype] $cmp);
+$crate::new_type_pair!(@layer $layer $otype $rtype [$stype] $s);
+$crate::new_type_pair!(@substrings $layer $cmp $closed $rtype);
+$crate::new_type_pair!(@closed $closed $rtype);
+$crate::new_type_pair!(@segments $segs $cmp $otype $rtype);
+$crate::new_type_pair!(@shared $otype $rtype [$stype] [$v $e $s $cmp $dbg $mask $layer $segs $closed $concat $builder $prefix $tokens]);
+$crate::new_type_pair!(@concat $concat $cmp $otype $rtype);
+$crate::new_type_pair!(@builder $builder $otype $rtype $cmp $layer);
+$crate::new_type_pair!(@debug $cmp $dbg $rtype);
+$crate::new_type_pair!(@debug $cmp $dbg $otype);
+
+$(#[$ometa])*
+pub struct $otype {
+    inner: $itype
+}
+
+impl $otype {
+    /// Creates a new type by consuming and validating `value` and then returning the wrapped value or an error
+    pub fn try_from(value: impl Into<$itype>) -> Result<Self, <$rtype as $crate::NewTypeRef>::ValidationError> {
+        let value = $otype { inner: value.into() };
+        <$rtype as $crate::NewTypeRef>::validate(value.inner.as_ref())?;
+        Ok(value)
+    }
+
+}
+
+impl ::std::ops::Deref for $otype {
+    type Target = $rtype;
 
-impl<'a> ::std::cmp::PartialOrd<$rtype> for &'a $stype {
     #[inline]
-    fn partial_cmp(&self, rhs: &$rtype) -> Option<::std::cmp::Ordering> {
-        ::std::cmp::PartialOrd::partial_cmp(*self, &rhs.inner)
+    fn deref(&self) -> &$rtype {
+        self.as_ref()
     }
 }
 
-impl ::std::cmp::PartialOrd<$stype> for $rtype {
+impl ::std::borrow::Borrow<$rtype> for $otype {
     #[inline]
-    fn partial_cmp(&self, rhs: &$stype) -> Option<::std::cmp::Ordering> {
-        ::std::cmp::PartialOrd::partial_cmp(&self.inner, rhs)
+    fn borrow(&self) -> &$rtype {
+        self.as_ref()
     }
 }
 
-impl<'a> ::std::cmp::PartialOrd<&'a $stype> for $rtype {
+$crate::new_type_pair!(@owned_cmp $cmp $otype $rtype [$stype]);
+$crate::new_type_pair!(@borrow $cmp $otype [$stype] |o| AsRef::<$stype>::as_ref(AsRef::<$rtype>::as_ref(o)));
+
+impl ::std::convert::AsRef<$rtype> for $otype {
     #[inline]
-    fn partial_cmp(&self, rhs: &&'a $stype) -> Option<::std::cmp::Ordering> {
-        ::std::cmp::PartialOrd::partial_cmp(&self.inner, *rhs)
+    fn as_ref(&self) -> &$rtype {
+        #[allow(unsafe_code)] unsafe { $rtype::from_unchecked(self.inner.as_ref()) }
     }
 }
 
-impl<'a> ::std::cmp::PartialOrd<$stype> for &'a $rtype {
+impl<'a> ::std::cmp::PartialEq<$otype> for &'a $rtype {
     #[inline]
-    fn partial_cmp(&self, rhs: &$stype) -> Option<::std::cmp::Ordering> {
-        ::std::cmp::PartialOrd::partial_cmp(&self.inner, rhs)
+    fn eq(&self, rhs: &$otype) -> bool {
+        *self == rhs.as_ref()
     }
 }
 
-impl<'a> From<&'a $rtype> for $otype {
+impl ::std::cmp::PartialEq<$otype> for $rtype {
     #[inline]
-    fn from(r: &'a $rtype) -> Self {
-        r.to_owned()
+    fn eq(&self, rhs: &$otype) -> bool {
+        self == rhs.as_ref()
     }
 }
 
-impl From<$otype> for $itype {
+impl<'a> ::std::cmp::PartialEq<$otype> for &'a $stype {
     #[inline]
-    fn from(o: $otype) -> Self {
-        o.inner
+    fn eq(&self, rhs: &$otype) -> bool {
+        self == rhs.as_ref()
     }
 }
 
-#[cfg(feature = "serde")]
-impl ::serde::Serialize for $otype {
-    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
-    where S: ::serde::Serializer {
-        ::serde::Serializer::serialize_str(serializer, AsRef::<$stype>::as_ref(AsRef::<$rtype>::as_ref(&self)))
+impl ::std::cmp::PartialEq<$otype> for $stype {
+    #[inline]
+    fn eq(&self, rhs: &$otype) -> bool {
+        self == rhs.as_ref()
     }
 }
 
-#[cfg(feature = "serde")]
-impl<'de> ::serde::Deserialize<'de> for $otype {
-    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where
-        D: ::serde::Deserializer<'de> {
-        let inner: $itype = ::serde::Deserialize::deserialize(deserializer)?;
-        Ok($otype::try_from(inner).map_err(|e| ::serde::de::Error::custom(e.to_string()))?)
+impl<'a> ::std::cmp::PartialEq<&'a $stype> for $otype {
+    #[inline]
+    fn eq(&self, rhs: &&'a $stype) -> bool {
+        self.as_ref() == *rhs
     }
 }
 
-#[cfg(feature = "serde")]
-impl ::serde::Serialize for $rtype {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where S: ::serde::Serializer {
-        ::serde::Serializer::serialize_str(serializer, AsRef::<$stype>::as_ref(&self))
+impl ::std::cmp::PartialEq<$stype> for $otype {
+    #[inline]
+    fn eq(&self, rhs: &$stype) -> bool {
+        self.as_ref() == *rhs
+    }
+}
+
+impl<'a> ::std::cmp::PartialEq<&'a $rtype> for $otype {
+    #[inline]
+    fn eq(&self, rhs: &&'a $rtype) -> bool {
+        self.as_ref() == *rhs
+    }
+}
+
+impl ::std::cmp::PartialEq<$rtype> for $otype {
+    #[inline]
+    fn eq(&self, rhs: &$rtype) -> bool {
+        self.as_ref() == rhs
+    }
+}
+
+impl<'a> From<&'a $rtype> for $otype {
+    #[inline]
+    fn from(r: &'a $rtype) -> Self {
+        $crate::NewTypeRef::to_owned(r)
     }
 }
 
+$crate::new_type_pair!(@into_inner $cmp $otype [$itype]);
+$crate::new_type_pair!(@serialize $cmp $otype |o| &AsRef::<$rtype>::as_ref(o).inner);
+
 #[cfg(feature = "serde")]
-impl<'de: 'a, 'a> ::serde::Deserialize<'de> for &'a $rtype {
+impl<'de> ::serde::Deserialize<'de> for $otype {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error> where
         D: ::serde::Deserializer<'de> {
-        let inner: &$stype = ::serde::Deserialize::deserialize(deserializer)?;
-        Ok($rtype::try_as_ref(inner).map_err(|e| ::serde::de::Error::custom(e.to_string()))?)
+        // Validates the string lent by the deserializer before copying it, so
+        // that invalid values are rejected without allocating for them.
+        // Strings handed over by the deserializer are moved into the wrapped
+        // value through its own `Deserialize`, which does not copy a `String`.
+        struct Visitor;
+
+        impl<'de> ::serde::de::Visitor<'de> for Visitor {
+            type Value = $otype;
+
+            fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.write_str(concat!("a valid ", stringify!($otype)))
+            }
+
+            fn visit_str<E: ::serde::de::Error>(self, value: &str) -> ::std::result::Result<$otype, E> {
+                let value = <$rtype as $crate::TryFromStr>::try_from_str(value).map_err(E::custom)?;
+                Ok($crate::NewTypeRef::to_owned(value))
+            }
+
+            fn visit_bytes<E: ::serde::de::Error>(self, value: &[u8]) -> ::std::result::Result<$otype, E> {
+                match ::std::str::from_utf8(value) {
+                    Ok(value) => self.visit_str(value),
+                    Err(_) => Err(E::invalid_value(::serde::de::Unexpected::Bytes(value), &self)),
+                }
+            }
+
+            $crate::new_type_pair!(@visit_owned $cmp $otype $rtype [$itype]);
+        }
+
+        ::serde::Deserializer::deserialize_str(deserializer, Visitor)
     }
 }
     };
+
+    (   $(#[$emeta:meta])*
+        pub enum $etype:ident;
+
+        $($pairs:tt)+
+    ) => {
+        $crate::new_type_pair!(@group [[$(#[$emeta])*] $etype] [] $($pairs)+);
+    };
+    ($($pairs:tt)+) => {
+        $crate::new_type_pair!(@group [] [] $($pairs)+);
+    };
 }
 
 #[cfg(test)]
@@ -436,7 +2133,7 @@ mod test {
     #[cfg(feature = "serde")]
     use bincode;
     use std::fmt;
-    use NewTypeRef;
+    use {AsciiCaseInsensitive, CaseInsensitive, Mask, NaturalOrder, NewTypeRef};
 
     #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct EmptyStringError;
@@ -457,54 +2154,216 @@ mod test {
         pub struct StrWrapRef(str);
     }
 
-    impl NewTypeRef for StrWrapRef {
-        type Owned = StrWrap;
+    impl NewTypeRef for StrWrapRef {
+        type Owned = StrWrap;
+        type InnerRef = str;
+        type ValidationError = EmptyStringError;
+
+        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
+            if value.is_empty() {
+                return Err(EmptyStringError);
+            }
+            Ok(())
+        }
+
+        fn to_owned(&self) -> Self::Owned {
+            let inner = String::from(&self.inner);
+            StrWrap { inner }
+        }
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        /// And now it's documented!
+        pub struct ArrStrWrap(ArrayString<[u8;16]>);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        /// Even the reference type is documented!
+        pub struct ArrStrWrapRef(str);
+    }
+
+    impl NewTypeRef for ArrStrWrapRef {
+        type Owned = ArrStrWrap;
+        type InnerRef = str;
+        type ValidationError = String;
+
+        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
+            if value.is_empty() {
+                return Err(String::from("Empty!"));
+            } else if value.len() > 16 {
+                return Err(String::from("Too Long!"));
+            }
+            Ok(())
+        }
+
+        fn to_owned(&self) -> Self::Owned {
+            let inner = ArrayString::from(self.as_ref()).unwrap();
+            ArrStrWrap { inner }
+        }
+    }
+
+    fn validate_lowercase(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() {
+            return Err("empty");
+        }
+        if value.chars().any(char::is_uppercase) {
+            return Err("uppercase");
+        }
+        Ok(())
+    }
+
+    fn validate_short(value: &str) -> Result<(), usize> {
+        if value.len() > 4 {
+            return Err(value.len());
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Lower(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct LowerRef(str) where validate = validate_lowercase, error = &'static str;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Unchecked(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct UncheckedRef(str);
+    }
+
+    impl NewTypeRef for UncheckedRef {
+        type Owned = Unchecked;
+        type InnerRef = str;
+        type ValidationError = EmptyStringError;
+
+        fn to_owned(&self) -> Self::Owned {
+            let inner = String::from(&self.inner);
+            Unchecked { inner }
+        }
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum GroupError;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct GroupLower(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct GroupLowerRef(str)
+        where
+            validate = validate_lowercase,
+            error = &'static str,
+        ;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct GroupShort(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct GroupShortRef(str) where error = usize, validate = validate_short;
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Shareable(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct ShareableRef(str)
+        where
+            validate = validate_lowercase,
+            error = &'static str,
+            shared = #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)] SharedShareable;
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug)]
+        pub struct Host(String);
+
+        #[derive(Debug)]
+        pub struct HostRef(str)
+        where
+            validate = validate_short,
+            error = usize,
+            compare = AsciiCaseInsensitive,
+            shared = #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)] SharedHost;
+
+        #[derive(Clone, Debug)]
+        pub struct Folded(String);
+
+        #[derive(Debug)]
+        pub struct FoldedRef(str) where compare = CaseInsensitive;
+    }
+
+    impl NewTypeRef for FoldedRef {
+        type Owned = Folded;
         type InnerRef = str;
         type ValidationError = EmptyStringError;
 
-        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
-            if value.is_empty() {
-                return Err(EmptyStringError);
-            }
-            Ok(())
+        fn to_owned(&self) -> Self::Owned {
+            let inner = String::from(&self.inner);
+            Folded { inner }
         }
+    }
 
-        fn to_owned(&self) -> Self::Owned {
-            let inner = String::from(self.as_ref());
-            StrWrap { inner }
+    fn validate_not_empty(value: &str) -> Result<(), EmptyStringError> {
+        if value.is_empty() {
+            return Err(EmptyStringError);
         }
+        Ok(())
     }
 
     new_type_pair! {
-        #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
-        /// And now it's documented!
-        pub struct ArrStrWrap(ArrayString<[u8;16]>);
+        #[derive(Clone)]
+        pub struct ApiKey(String);
 
-        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
-        /// Even the reference type is documented!
-        pub struct ArrStrWrapRef(str);
-    }
+        pub struct ApiKeyRef(str) where validate = validate_not_empty, error = EmptyStringError, secret;
 
-    impl NewTypeRef for ArrStrWrapRef {
-        type Owned = ArrStrWrap;
-        type InnerRef = str;
-        type ValidationError = String;
+        pub struct Password(String);
 
-        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
-            if value.is_empty() {
-                return Err(String::from("Empty!"));
-            } else if value.len() > 16 {
-                return Err(String::from("Too Long!"));
-            }
-            Ok(())
-        }
+        pub struct PasswordRef(str)
+        where
+            validate = validate_not_empty,
+            error = EmptyStringError,
+            secret = serialize;
+    }
 
-        fn to_owned(&self) -> Self::Owned {
-            let inner = ArrayString::from(self.as_ref()).unwrap();
-            ArrStrWrap { inner }
+    fn mask_local_part(value: &str, f: &mut fmt::Formatter) -> fmt::Result {
+        match value.find('@') {
+            Some(at) => write!(f, "***{}", &value[at..]),
+            None => f.write_str("***"),
         }
     }
 
+    new_type_pair! {
+        #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Phone(String);
+
+        #[derive(Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct PhoneRef(str)
+        where
+            validate = validate_not_empty,
+            error = EmptyStringError,
+            mask = Mask::Keep { first: 3, last: 2 },
+            debug = masked;
+
+        #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Email(String);
+
+        #[derive(Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct EmailRef(str)
+        where validate = validate_not_empty, error = EmptyStringError, mask = Mask::With(mask_local_part), debug = masked;
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug)]
+        pub struct FileName(String);
+
+        #[derive(Debug)]
+        pub struct FileNameRef(str) where validate = validate_not_empty, error = EmptyStringError, compare = NaturalOrder;
+    }
+
     #[test]
     fn minimal() {
         assert!(StrWrap::try_from("x").is_ok());
@@ -512,6 +2371,7 @@ mod test {
     }
 
     const TEST_STRING: &str = "TESTING";
+    #[cfg(feature = "serde")]
     const ALT_STRING: &str = "Ĉu ĝustas?";
 
     #[cfg(feature = "serde")]
@@ -519,6 +2379,207 @@ mod test {
         static ref SERIALIZED_TEST_STRING: Vec<u8> = bincode::serialize(TEST_STRING).unwrap();
     }
 
+    #[test]
+    fn multiple_pairs_validate_independently() {
+        assert_eq!(Err("uppercase"), Lower::try_from("Lower"));
+        assert_eq!(Err("empty"), LowerRef::try_as_ref("").map(|_| ()));
+        assert_eq!("lower", Lower::try_from("lower").unwrap());
+        assert_eq!("", UncheckedRef::try_as_ref("").unwrap());
+        assert_eq!(Lower::from(LowerRef::try_as_ref("x").unwrap()), "x");
+    }
+
+    #[test]
+    fn group_errors_identify_the_pair() {
+        assert_eq!(Err(GroupError::GroupLower("empty")), GroupLower::try_from(""));
+        assert_eq!(
+            Err(GroupError::GroupShort(6)),
+            GroupShortRef::try_as_ref("abcdef").map(|_| ())
+        );
+        assert_eq!("uppercase", GroupLowerRef::try_as_ref("A").unwrap_err().to_string());
+        assert_eq!("6", GroupShort::try_from("abcdef").unwrap_err().to_string());
+        assert_eq!("abcd", GroupShort::try_from("abcd").unwrap());
+    }
+
+    #[test]
+    fn shared_conversions() {
+        let shared = SharedShareable::try_from(TEST_STRING.to_lowercase().as_str()).unwrap();
+        let owned: Shareable = Shareable::from(&shared);
+        let reference: &ShareableRef = &shared;
+        assert_eq!(owned, reference);
+        assert_eq!(shared, SharedShareable::from(reference));
+        assert_eq!(shared, SharedShareable::from(owned.clone()));
+        assert_eq!(owned, Shareable::from(shared.clone()));
+        assert_eq!(Err("uppercase"), SharedShareable::try_from(TEST_STRING));
+    }
+
+    #[test]
+    fn shared_clones_share_the_allocation() {
+        use std::thread;
+
+        let shared = SharedShareable::try_from("shared").unwrap();
+        let clone = shared.clone();
+        assert!(::std::ptr::eq::<ShareableRef>(&*shared, &*clone));
+        let from_thread = thread::spawn(move || String::from(AsRef::<str>::as_ref(&clone)))
+            .join()
+            .unwrap();
+        assert_eq!(from_thread, "shared");
+    }
+
+    #[test]
+    fn shared_comparisons() {
+        use std::cmp::{Ordering, PartialOrd};
+        const LESS: Option<Ordering> = Some(Ordering::Less);
+        const GREATER: Option<Ordering> = Some(Ordering::Greater);
+        let s = "shared";
+        let shared = SharedShareable::try_from(s).unwrap();
+        let reference = ShareableRef::try_as_ref(s).unwrap();
+        let owned = Shareable::try_from(s).unwrap();
+        assert_eq!(shared, s);
+        assert_eq!(shared, *s);
+        assert_eq!(shared, reference);
+        assert_eq!(shared, *reference);
+        assert_eq!(shared, owned);
+        assert_eq!(s, shared);
+        assert_eq!(*s, shared);
+        assert_eq!(reference, shared);
+        assert_eq!(*reference, shared);
+        assert_eq!(owned, shared);
+        let later = SharedShareable::try_from("sharedz").unwrap();
+        assert_eq!(LESS, PartialOrd::partial_cmp(&shared, &later));
+        assert_eq!(LESS, PartialOrd::partial_cmp(&s, &later));
+        assert_eq!(LESS, PartialOrd::partial_cmp(s, &later));
+        assert_eq!(LESS, PartialOrd::partial_cmp(&reference, &later));
+        assert_eq!(LESS, PartialOrd::partial_cmp(reference, &later));
+        assert_eq!(LESS, PartialOrd::partial_cmp(&owned, &later));
+        assert_eq!(GREATER, PartialOrd::partial_cmp(&later, &s));
+        assert_eq!(GREATER, PartialOrd::partial_cmp(&later, s));
+        assert_eq!(GREATER, PartialOrd::partial_cmp(&later, &reference));
+        assert_eq!(GREATER, PartialOrd::partial_cmp(&later, reference));
+        assert_eq!(GREATER, PartialOrd::partial_cmp(&later, &owned));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn shared_roundtrips() {
+        let shared = SharedShareable::try_from("shared").unwrap();
+        let serialized = bincode::serialize(&shared).expect("serialization to succeed");
+        assert_eq!(bincode::serialize("shared").unwrap(), serialized);
+        let value: SharedShareable =
+            bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(shared, value);
+    }
+
+    #[test]
+    fn case_insensitive_comparisons() {
+        use std::cmp::Ordering;
+        let host = Host::try_from("WWW").unwrap();
+        let reference = HostRef::try_as_ref("www").unwrap();
+        assert_eq!(host, reference);
+        assert_eq!(reference, host);
+        assert_eq!(host, Host::try_from("wWw").unwrap());
+        assert_eq!(host, "www");
+        assert_eq!("Www", *reference);
+        assert_eq!(SharedHost::from(reference), host);
+        assert_ne!(HostRef::try_as_ref("ÅS").unwrap(), "ås");
+        assert_eq!(FoldedRef::try_as_ref("ÅSA").unwrap(), "åsa");
+        assert_eq!(Some(Ordering::Less), HostRef::try_as_ref("B").unwrap().partial_cmp("c"));
+        assert_eq!(Ordering::Greater, Ord::cmp(&host, &Host::try_from("abc").unwrap()));
+        assert_eq!("www", host.canonical());
+        assert_eq!("WWW", AsRef::<str>::as_ref(&*host));
+    }
+
+    #[test]
+    fn case_insensitive_lookups() {
+        use std::collections::{BTreeSet, HashMap};
+        let mut hosts = HashMap::new();
+        hosts.insert(Host::try_from("Mail").unwrap(), 25);
+        assert_eq!(Some(&25), hosts.get(HostRef::try_as_ref("MAIL").unwrap()));
+        assert_eq!(Some(&25), hosts.get(&Host::try_from("mail").unwrap()));
+        let folded: BTreeSet<Folded> = ["Straße", "STRASSE", "strasse", "Weg"]
+            .iter()
+            .map(|s| Folded::try_from(*s).unwrap())
+            .collect();
+        assert_eq!(2, folded.len());
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let key = ApiKey::try_from("hunter2").unwrap();
+        assert_eq!("ApiKey(<redacted>)", format!("{:?}", key));
+        assert_eq!("ApiKeyRef(<redacted>)", format!("{:?}", &*key));
+        assert_eq!("<redacted>", key.to_string());
+        assert_eq!("hunter2", key.expose_secret());
+        assert_eq!(Err(EmptyStringError), ApiKeyRef::try_as_ref("").map(|_| ()));
+    }
+
+    #[test]
+    fn secrets_compare_for_equality() {
+        let key = ApiKey::try_from("hunter2").unwrap();
+        let reference = ApiKeyRef::try_as_ref("hunter2").unwrap();
+        assert_eq!(key, reference);
+        assert_eq!(reference, key);
+        assert_eq!(key, key.clone());
+        assert_eq!(key, "hunter2");
+        assert_eq!("hunter2", *reference);
+        assert!(key != "hunter3");
+        assert!(reference != ApiKeyRef::try_as_ref("hunter").unwrap());
+        let password = Password::try_from("swordfish").unwrap();
+        assert_eq!(password, "swordfish");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn secrets_serialize_when_declared() {
+        let password = Password::try_from("swordfish").unwrap();
+        let serialized = bincode::serialize(&password).expect("serialization to succeed");
+        assert_eq!(bincode::serialize("swordfish").unwrap(), serialized);
+        let value: Password = bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(password, value);
+        let key: ApiKey = bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(key, "swordfish");
+    }
+
+    #[test]
+    fn masked_values() {
+        let phone = Phone::try_from("+441234567").unwrap();
+        assert_eq!("+44*****67", phone.masked().to_string());
+        assert_eq!("Phone(+44*****67)", format!("{:?}", phone));
+        assert_eq!("PhoneRef(+44*****67)", format!("{:?}", &*phone));
+        let email = EmailRef::try_as_ref("ada@example.com").unwrap();
+        assert_eq!("EmailRef(***@example.com)", format!("{:?}", email));
+        assert_eq!("*******", StrWrapRef::try_as_ref(TEST_STRING).unwrap().masked().to_string());
+    }
+
+    #[test]
+    fn natural_ordering() {
+        use std::cmp::Ordering;
+        let mut files: Vec<FileName> = ["item10", "item9", "item1"]
+            .iter()
+            .map(|f| FileName::try_from(*f).unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["item1", "item9", "item10"]);
+        let nine = FileNameRef::try_as_ref("item9").unwrap();
+        assert_eq!(Some(Ordering::Less), nine.partial_cmp("item10"));
+        assert_eq!(Some(Ordering::Greater), "item10".partial_cmp(nine));
+        assert_eq!(Some(Ordering::Less), PartialOrd::partial_cmp(&files[1], "item10"));
+        assert_eq!(Some(Ordering::Less), PartialOrd::partial_cmp(nine, &files[2]));
+        assert_eq!(files[1], nine);
+        assert!(files[1] != "item09");
+    }
+
+    proptest! {
+        #[test]
+        fn natural_ordering_agrees_with_equality(ref a in "[a0-2]{1,5}", ref b in "[a0-2]{1,5}") {
+            use std::cmp::Ordering;
+            let owned = FileName::try_from(a.as_str()).unwrap();
+            let reference = FileNameRef::try_as_ref(b).unwrap();
+            assert_eq!(owned == reference, owned.partial_cmp(reference) == Some(Ordering::Equal));
+            assert_eq!(owned == b.as_str(), owned.partial_cmp(b.as_str()) == Some(Ordering::Equal));
+            assert_eq!(*a == *b, owned == reference);
+        }
+    }
+
     #[test]
     fn equality() {
         assert_eq!(TEST_STRING, StrWrap::try_from(TEST_STRING).unwrap());
@@ -686,6 +2747,7 @@ mod test {
     }
 
     #[test]
+    #[allow(unused_must_use, clippy::size_of_ref)]
     fn sizes_of_types_match_expectations() {
         use std::mem::size_of_val;
         const TEST_STR: &str = "TESTING!";
@@ -763,4 +2825,406 @@ mod test {
         }
     }
 
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum MailboxError {
+        Empty,
+        MissingAt,
+    }
+
+    impl From<EmptyStringError> for MailboxError {
+        fn from(_: EmptyStringError) -> Self {
+            MailboxError::Empty
+        }
+    }
+
+    impl fmt::Display for MailboxError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(match *self {
+                MailboxError::Empty => "empty",
+                MailboxError::MissingAt => "missing @",
+            })
+        }
+    }
+
+    fn validate_mailbox(value: &NonEmptyRef) -> Result<(), MailboxError> {
+        if !AsRef::<str>::as_ref(value).contains('@') {
+            return Err(MailboxError::MissingAt);
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct NonEmpty(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct NonEmptyRef(str) where validate = validate_not_empty, error = EmptyStringError;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Mailbox(NonEmpty);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct MailboxRef(NonEmptyRef) where validate = validate_mailbox, error = MailboxError;
+    }
+
+    #[test]
+    fn layered_pairs_validate_each_layer() {
+        use TryFromStr;
+        assert_eq!(Err(MailboxError::Empty), MailboxRef::try_from_str("").map(|_| ()));
+        assert_eq!(Err(MailboxError::MissingAt), MailboxRef::try_from_str("ada").map(|_| ()));
+        let mailbox = MailboxRef::try_from_str("ada@example.com").unwrap();
+        assert_eq!("ada@example.com", AsRef::<str>::as_ref(mailbox));
+        assert_eq!("*".repeat(15), mailbox.masked().to_string());
+
+        let inner = NonEmptyRef::try_as_ref("ada@example.com").unwrap();
+        assert_eq!(inner, AsRef::<NonEmptyRef>::as_ref(mailbox));
+        assert_eq!(mailbox, MailboxRef::try_as_ref(inner).unwrap());
+        assert_eq!(Err(MailboxError::MissingAt), Mailbox::try_from(NonEmpty::try_from("ada").unwrap()));
+
+        let owned = Mailbox::from(mailbox);
+        assert_eq!(owned, mailbox);
+        assert_eq!(*inner, *owned);
+        assert_eq!(NonEmpty::from(inner), NonEmpty::from(owned));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn layered_pairs_roundtrip() {
+        let mailbox = Mailbox::try_from(NonEmpty::try_from("ada@example.com").unwrap()).unwrap();
+        let serialized = bincode::serialize(&mailbox).expect("serialization to succeed");
+        assert_eq!(bincode::serialize("ada@example.com").unwrap(), serialized);
+        let value: Mailbox = bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(mailbox, value);
+        let value: &MailboxRef = bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(mailbox, value);
+        assert!(bincode::deserialize::<Mailbox>(&bincode::serialize("").unwrap()).is_err());
+        assert!(bincode::deserialize::<Mailbox>(&bincode::serialize("ada").unwrap()).is_err());
+    }
+
+    fn validate_label(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit()) {
+            return Err("invalid label");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Label(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct LabelRef(str) where validate = validate_label, error = &'static str;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Service(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct ServiceRef(str) where segments = LabelRef, separator = '.';
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Name(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct NameRef(str) where validate = validate_name, error = &'static str;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Path(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct PathRef(str) where segments = NameRef, separator = '/';
+    }
+
+    fn validate_name(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || value.contains(char::is_whitespace) {
+            return Err("invalid name");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn segmented_values_validate_each_segment() {
+        assert_eq!(Err("invalid label"), ServiceRef::try_as_ref("").map(|_| ()));
+        assert_eq!(Err("invalid label"), ServiceRef::try_as_ref("org..api").map(|_| ()));
+        assert_eq!(Err("invalid label"), ServiceRef::try_as_ref("org.Team").map(|_| ()));
+        assert!(ServiceRef::try_as_ref("org").is_ok());
+    }
+
+    #[test]
+    fn segmented_values_have_segments() {
+        let service = ServiceRef::try_as_ref("org.team.api").unwrap();
+        let segments: Vec<&LabelRef> = service.segments().collect();
+        assert_eq!(segments, ["org", "team", "api"]);
+        assert_eq!("org", service.first_segment());
+        assert_eq!("api", service.last_segment());
+
+        let team = service.parent().unwrap();
+        assert_eq!("org.team", team);
+        assert_eq!("org", team.parent().unwrap());
+        assert_eq!(None, team.parent().unwrap().parent());
+        assert_eq!("org", ServiceRef::try_as_ref("org").unwrap().last_segment());
+
+        assert!(service.starts_with(team));
+        assert!(service.starts_with(service));
+        assert!(!team.starts_with(service));
+        assert!(!ServiceRef::try_as_ref("org.teams").unwrap().starts_with(team));
+
+        let web = team.join(LabelRef::try_as_ref("web").unwrap()).unwrap();
+        assert_eq!("org.team.web", web);
+        assert_eq!(Some(team), web.parent());
+    }
+
+    #[test]
+    fn joins_validate_segments_holding_the_separator() {
+        let docs = PathRef::try_as_ref("docs").unwrap();
+        let nested = docs.join(NameRef::try_as_ref("api/v1").unwrap()).unwrap();
+        assert_eq!("docs/api/v1", nested);
+        assert_eq!(3, nested.segments().count());
+        assert_eq!(Err("invalid name"), docs.join(NameRef::try_as_ref("api/").unwrap()));
+        assert_eq!(Err("invalid name"), docs.join(NameRef::try_as_ref("/api").unwrap()));
+    }
+
+    proptest! {
+        #[test]
+        fn parents_and_joins_are_valid(ref s in "[a-z]{1,3}(\\.[a-z0-9]{1,3}){0,4}") {
+            let service = ServiceRef::try_as_ref(s).unwrap();
+            if let Some(parent) = service.parent() {
+                assert!(ServiceRef::try_as_ref(parent).is_ok());
+                assert_eq!(Ok(service), parent.join(service.last_segment()).as_ref().map(AsRef::<ServiceRef>::as_ref));
+                assert!(service.starts_with(parent));
+            }
+            assert_eq!(s.split('.').count(), service.segments().count());
+        }
+    }
+
+    fn validate_no_spaces(value: &str) -> Result<(), &'static str> {
+        if value.contains(' ') {
+            return Err("contains spaces");
+        }
+        Ok(())
+    }
+
+    fn validate_capitalized(value: &str) -> Result<(), &'static str> {
+        if !value.starts_with(|c: char| c.is_ascii_uppercase()) {
+            return Err("not capitalized");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Token(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct TokenRef(str) where validate = validate_no_spaces, error = &'static str, unsafe substring_closed;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Title(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct TitleRef(str) where validate = validate_capitalized, error = &'static str;
+    }
+
+    #[test]
+    fn slices_are_revalidated() {
+        let title = TitleRef::try_as_ref("  Ĉu Jes  ").unwrap_err();
+        assert_eq!("not capitalized", title);
+        let title = TitleRef::try_as_ref("Ĉu Jes").unwrap_err();
+        assert_eq!("not capitalized", title);
+        let title = TitleRef::try_as_ref("Jes Ĉu").unwrap();
+        assert_eq!(Some("Jes"), title.get(..3).map(AsRef::<str>::as_ref));
+        assert_eq!(None, title.get(1..3));
+        assert_eq!(None, title.get(..5));
+        assert_eq!(None, title.get(..42));
+        let (head, tail) = title.split_at(3);
+        assert_eq!(Ok("Jes"), head.map(AsRef::<str>::as_ref));
+        assert_eq!(Err(" Ĉu"), tail.map(AsRef::<str>::as_ref));
+        assert_eq!(Some(Err("es Ĉu")), title.strip_prefix("J").map(|r| r.map(AsRef::<str>::as_ref)));
+        assert_eq!(Some(Ok("Jes")), title.strip_suffix(" Ĉu").map(|r| r.map(AsRef::<str>::as_ref)));
+        assert_eq!(None, title.strip_prefix("Ĉu"));
+        let padded = TitleRef::try_as_ref("Jes ").unwrap();
+        assert_eq!(Ok("Jes"), padded.trim().map(AsRef::<str>::as_ref));
+        assert_eq!(Ok("Jes "), padded.trim_start().map(AsRef::<str>::as_ref));
+    }
+
+    #[test]
+    fn substring_closed_slices_are_not_revalidated() {
+        let token = TokenRef::try_as_ref("abc").unwrap();
+        assert_eq!(Some("b"), token.get(1..2).map(AsRef::<str>::as_ref));
+        assert_eq!(Some(""), token.get(3..).map(AsRef::<str>::as_ref));
+        assert_eq!(Some(Ok("bc")), token.strip_prefix("a").map(|r| r.map(AsRef::<str>::as_ref)));
+    }
+
+    proptest! {
+        #[test]
+        fn slices_are_valid(ref s in "[A-Za-z ]{0,8}", start in 0usize..8, end in 0usize..8) {
+            if let Ok(title) = TitleRef::try_as_ref(s) {
+                if let Some(slice) = title.get(start..end) {
+                    assert!(TitleRef::try_as_ref(AsRef::<str>::as_ref(slice)).is_ok());
+                }
+                if let Ok(trimmed) = title.trim() {
+                    assert_eq!(s.trim(), AsRef::<str>::as_ref(trimmed));
+                }
+            }
+            if let Ok(token) = TokenRef::try_as_ref(s) {
+                if let Some(slice) = token.get(start..end) {
+                    assert!(TokenRef::try_as_ref(AsRef::<str>::as_ref(slice)).is_ok());
+                }
+            }
+        }
+    }
+
+    fn validate_pin_prefix(value: &str) -> Result<(), &'static str> {
+        if value.len() > 6 || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err("invalid pin");
+        }
+        Ok(())
+    }
+
+    fn validate_pin(value: &str) -> Result<(), &'static str> {
+        validate_pin_prefix(value)?;
+        if value.len() < 4 {
+            return Err("short pin");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Pin(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct PinRef(str)
+        where
+            validate = validate_pin,
+            error = &'static str,
+            validate_prefix = validate_pin_prefix,
+            builder = #[derive(Debug)] PinBuilder;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct PinPath(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct PinPathRef(str) where segments = PinRef, separator = '.', builder = PinPathBuilder;
+    }
+
+    #[test]
+    fn builders_reject_dead_prefixes() {
+        let mut builder = PinBuilder::default();
+        assert_eq!(Ok(()), builder.push_str("12"));
+        assert_eq!(Err("short pin"), PinRef::try_as_ref(builder.as_str()).map(|_| ()));
+        assert_eq!(Err("invalid pin"), builder.push('a'));
+        assert_eq!(Err("invalid pin"), builder.push_str("34567"));
+        assert_eq!("12", builder.as_str());
+        assert_eq!(Ok(()), builder.push_str("34"));
+        assert_eq!("1234", builder.finish().unwrap());
+    }
+
+    #[test]
+    fn segmented_builders_check_each_segment() {
+        let mut builder = PinPathBuilder::new();
+        assert_eq!(Ok(()), builder.push_str("1234.56"));
+        assert_eq!(Err("invalid pin"), builder.push('x'));
+        assert_eq!(Err("short pin"), builder.push('.'));
+        assert_eq!("1234.56", builder.as_str());
+        assert_eq!(Ok(()), builder.push_str("78"));
+        assert_eq!("1234.5678", builder.finish().unwrap());
+    }
+
+    proptest! {
+        #[test]
+        fn prefixes_of_valid_values_are_accepted(ref s in "[0-9]{4,6}(\\.[0-9]{4,6}){0,2}") {
+            let mut builder = PinPathBuilder::new();
+            for c in s.chars() {
+                assert_eq!(Ok(()), builder.push(c));
+            }
+            assert_eq!(Ok(PinPath::try_from(s.as_str()).unwrap()), builder.finish());
+        }
+    }
+
+    fn validate_ticket_id(value: &str) -> Result<(), &'static str> {
+        let mut parts = value.splitn(2, '-');
+        let project = parts.next().unwrap_or("");
+        let number = parts.next().unwrap_or("");
+        if project.is_empty() || !project.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err("invalid project");
+        }
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return Err("invalid number");
+        }
+        Ok(())
+    }
+
+    fn next_hashtag(text: &str, from: usize) -> Option<::std::ops::Range<usize>> {
+        let start = from + text[from..].find('#')?;
+        let end = text[start + 1..].find('#').map_or(text.len(), |i| start + 1 + i);
+        Some(start..end)
+    }
+
+    fn validate_hashtag(value: &str) -> Result<(), &'static str> {
+        if value.len() < 2 || !value[1..].chars().all(char::is_alphanumeric) {
+            return Err("invalid hashtag");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct TicketId(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct TicketIdRef(str) where validate = validate_ticket_id, error = &'static str;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Hashtag(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct HashtagRef(str) where validate = validate_hashtag, error = &'static str, tokenize = next_hashtag;
+    }
+
+    #[test]
+    fn finds_values_between_words() {
+        let text = "Fixed WEB-12 (and API-7), but not web-3 or OPS-.\nSee DB-100.";
+        let found: Vec<_> = TicketIdRef::find_iter(text).collect();
+        assert_eq!(3, found.len());
+        assert_eq!((6..12, "WEB-12"), (found[0].0.clone(), AsRef::<str>::as_ref(found[0].1)));
+        assert_eq!("API-7", found[1].1);
+        assert_eq!("DB-100", found[2].1);
+        assert_eq!("DB-100", &text[found[2].0.clone()]);
+        assert_eq!(0, TicketIdRef::find_iter("").count());
+        assert_eq!(0, TicketIdRef::find_iter(" \t.,; ").count());
+    }
+
+    #[test]
+    fn finds_values_with_custom_tokens() {
+        let found: Vec<_> = HashtagRef::find_iter("tags:#rust#ĝuste# #macros").map(|(range, tag)| (range, AsRef::<str>::as_ref(tag))).collect();
+        assert_eq!(vec![(5..10, "#rust"), (10..17, "#ĝuste"), (19..26, "#macros")], found);
+    }
+
+    proptest! {
+        #[test]
+        fn found_values_are_valid_slices(ref s in "([A-Z]{1,3}-[0-9]{1,3}|[a-z]{1,3}|[ ,.()-]){0,8}") {
+            let mut end = 0;
+            for (range, id) in TicketIdRef::find_iter(s) {
+                assert!(range.start >= end);
+                end = range.end;
+                assert_eq!(&s[range], AsRef::<str>::as_ref(id));
+                assert!(TicketIdRef::try_as_ref(AsRef::<str>::as_ref(id)).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn validates_values_in_bulk() {
+        let values = ["lower", "Upper", "", "again"];
+        let results = LowerRef::try_as_ref_all(&values);
+        assert_eq!(4, results.len());
+        assert_eq!(Ok("lower"), results[0].map(AsRef::<str>::as_ref));
+        assert_eq!(Err("uppercase"), results[1].map(|_| ()));
+        assert_eq!(Err("empty"), results[2].map(|_| ()));
+        assert_eq!(Ok("again"), results[3].map(AsRef::<str>::as_ref));
+        let owned = [String::from("layered")];
+        let owned: Vec<&String> = owned.iter().collect();
+        assert!(LowerRef::try_as_ref_all(&owned)[0].is_ok());
+        assert!(LowerRef::try_as_ref_all::<str>(&[]).is_empty());
+    }
 }
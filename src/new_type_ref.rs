@@ -0,0 +1,158 @@
+#[macro_export]
+/// Creates a reference new type wrapping `str` without a matching owned
+/// wrapper. The reference type must implement `NewTypeRef`.
+///
+/// This generates the same validation, conversions, comparisons, and serde
+/// implementations that `new_type_pair!` generates between the reference type
+/// and the type it wraps. Since there is no owned wrapper, `Box<Self>` is a
+/// natural choice for `NewTypeRef::Owned`, and a `From<&Self>` conversion is
+/// generated for it.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::NewTypeRef;
+///
+/// new_type_ref! {
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     /// A country code from a static lookup table
+///     pub struct CountryCodeRef(str);
+/// }
+///
+/// impl NewTypeRef for CountryCodeRef {
+///     type Owned = Box<CountryCodeRef>;
+///     type InnerRef = str;
+///     type ValidationError = &'static str;
+///
+///     fn validate(value: &str) -> Result<(), Self::ValidationError> {
+///         if value.len() != 2 || !value.chars().all(|c| c.is_ascii_uppercase()) {
+///             return Err("country codes are two uppercase letters");
+///         }
+///         Ok(())
+///     }
+///
+///     fn to_owned(&self) -> Self::Owned {
+///         self.into()
+///     }
+/// }
+///
+/// # pub fn main() {
+/// let codes: Vec<&CountryCodeRef> = ["DE", "FR", "US"]
+///     .iter()
+///     .map(|c| CountryCodeRef::try_as_ref(c).unwrap())
+///     .collect();
+/// assert!(CountryCodeRef::try_as_ref("usa").is_err());
+/// assert_eq!("FR", codes[1]);
+/// assert_eq!(codes[2], &*codes[2].to_owned());
+/// # }
+/// ```
+macro_rules! new_type_ref {
+    ($(
+        $(#[$rmeta:meta])*
+        pub struct $rtype:ident(str);
+    )+) => {$(
+$crate::new_type_pair!(@ref [$(#[$rmeta])*] $rtype [str] []);
+$crate::new_type_pair!(@substrings [] [] [] $rtype);
+
+impl<'a> From<&'a $rtype> for Box<$rtype> {
+    #[inline]
+    fn from(r: &'a $rtype) -> Self {
+        let inner: Box<str> = From::from(&r.inner);
+        #[allow(unsafe_code)] unsafe { Box::from_raw(Box::into_raw(inner) as *mut $rtype) }
+    }
+}
+
+impl $crate::TransparentOwned for Box<$rtype> {
+    type Inner = Box<str>;
+
+    #[inline]
+    fn into_inner(self) -> Box<str> {
+        #[allow(unsafe_code)] unsafe { Box::from_raw(Box::into_raw(self) as *mut str) }
+    }
+
+    #[inline]
+    #[allow(unsafe_code)]
+    unsafe fn from_inner_unchecked(inner: Box<str>) -> Box<$rtype> {
+        Box::from_raw(Box::into_raw(inner) as *mut $rtype)
+    }
+}
+    )+};
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "serde")]
+    use bincode;
+    use {NewTypeRef, TransparentOwned};
+
+    new_type_ref! {
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        /// A reference-only new type
+        pub struct LabelRef(str);
+    }
+
+    impl NewTypeRef for LabelRef {
+        type Owned = Box<LabelRef>;
+        type InnerRef = str;
+        type ValidationError = &'static str;
+
+        fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
+            if value.contains(char::is_whitespace) {
+                return Err("Whitespace!");
+            }
+            Ok(())
+        }
+
+        fn to_owned(&self) -> Self::Owned {
+            self.into()
+        }
+    }
+
+    const TEST_STRING: &str = "TESTING";
+
+    #[test]
+    fn validates() {
+        assert_eq!(Err("Whitespace!"), LabelRef::try_as_ref("a b").map(|_| ()));
+        assert_eq!(TEST_STRING, LabelRef::try_as_ref(TEST_STRING).unwrap());
+    }
+
+    #[test]
+    fn compares_with_inner() {
+        use std::cmp::Ordering;
+        let label = LabelRef::try_as_ref(TEST_STRING).unwrap();
+        assert_eq!(label, TEST_STRING);
+        assert_eq!(*label, *TEST_STRING);
+        assert_eq!(TEST_STRING, *label);
+        assert_eq!(Some(Ordering::Less), label.partial_cmp("U"));
+        assert_eq!(Some(Ordering::Greater), "U".partial_cmp(label));
+    }
+
+    #[test]
+    fn boxed_roundtrip() {
+        let label = LabelRef::try_as_ref(TEST_STRING).unwrap();
+        let boxed: Box<LabelRef> = label.to_owned();
+        assert_eq!(label, &*boxed);
+        let inner: &str = (*boxed).as_ref();
+        assert_eq!(TEST_STRING, inner);
+        let inner = TransparentOwned::into_inner(boxed);
+        assert_eq!(TEST_STRING, &*inner);
+        #[allow(unsafe_code)]
+        let boxed: Box<LabelRef> = unsafe { TransparentOwned::from_inner_unchecked(inner) };
+        assert_eq!(label, &*boxed);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn label_ref_roundtrips() {
+        let label = LabelRef::try_as_ref(TEST_STRING).unwrap();
+        let serialized = bincode::serialize(label).expect("serialization to succeed");
+        assert_eq!(bincode::serialize(TEST_STRING).unwrap(), serialized);
+        let value: &LabelRef =
+            bincode::deserialize(&serialized).expect("deserialization to succeed");
+        assert_eq!(label, value);
+    }
+}
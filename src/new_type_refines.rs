@@ -0,0 +1,211 @@
+#[macro_export]
+/// Declares that every valid value of one reference type is also a valid
+/// value of another, which both wrap the same type.
+///
+/// This implements `Refines`, along with infallible conversions from the
+/// refined reference and owned types to those of the parent, and fallible
+/// conversions back through `TryFrom`, which validate the value as the
+/// refined type. Upcasts do not validate the value again, except in builds
+/// with debug assertions, where they panic if the relation does not hold, so
+/// the relation is an unchecked promise declared with `unsafe impl`. Owned
+/// values are converted in both directions without copying them, which
+/// requires the owned types to implement `TransparentOwned`.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use std::convert::TryFrom;
+///
+/// fn validate_id(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() || value.len() > 64 {
+///         return Err("ids have between 1 and 64 bytes");
+///     }
+///     Ok(())
+/// }
+///
+/// fn validate_short_id(value: &str) -> Result<(), &'static str> {
+///     if value.is_empty() || value.len() > 8 {
+///         return Err("short ids have between 1 and 8 bytes");
+///     }
+///     Ok(())
+/// }
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Id(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct IdRef(str) where validate = validate_id, error = &'static str;
+///
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct ShortId(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct ShortIdRef(str) where validate = validate_short_id, error = &'static str;
+/// }
+///
+/// new_type_refines! {
+///     unsafe impl Refines<IdRef> for ShortIdRef;
+/// }
+///
+/// # pub fn main() {
+/// let short = ShortId::try_from("a1b2").unwrap();
+/// let id: &IdRef = From::from(&*short);
+/// assert_eq!("a1b2", id);
+/// assert_eq!(Id::try_from("a1b2").unwrap(), Id::from(short));
+///
+/// let long = IdRef::try_as_ref("0123456789").unwrap();
+/// assert!(<&ShortIdRef>::try_from(long).is_err());
+/// # }
+/// ```
+macro_rules! new_type_refines {
+    ($(
+        unsafe impl Refines<$parent:ty> for $child:ty;
+    )+) => {$(
+#[allow(unsafe_code)]
+unsafe impl $crate::Refines<$parent> for $child {
+    #[inline]
+    fn upcast(&self) -> &$parent {
+        let inner = ::std::convert::AsRef::<<$child as $crate::NewTypeRef>::InnerRef>::as_ref(self);
+        debug_assert!(
+            <$parent as $crate::NewTypeRef>::validate(inner).is_ok(),
+            concat!("a valid ", stringify!($child), " is not a valid ", stringify!($parent))
+        );
+        #[allow(unsafe_code)] unsafe { <$parent as $crate::TransparentRef>::from_inner_unchecked(inner) }
+    }
+}
+
+impl<'a> From<&'a $child> for &'a $parent {
+    #[inline]
+    fn from(child: &'a $child) -> Self {
+        $crate::Refines::upcast(child)
+    }
+}
+
+impl From<<$child as $crate::NewTypeRef>::Owned> for <$parent as $crate::NewTypeRef>::Owned {
+    #[inline]
+    fn from(child: <$child as $crate::NewTypeRef>::Owned) -> Self {
+        let _: &$parent = $crate::Refines::upcast(::std::convert::AsRef::<$child>::as_ref(&child));
+        let inner = $crate::TransparentOwned::into_inner(child);
+        #[allow(unsafe_code)] unsafe { $crate::TransparentOwned::from_inner_unchecked(From::from(inner)) }
+    }
+}
+
+impl<'a> ::std::convert::TryFrom<&'a $parent> for &'a $child {
+    type Error = <$child as $crate::NewTypeRef>::ValidationError;
+
+    #[inline]
+    fn try_from(parent: &'a $parent) -> Result<Self, Self::Error> {
+        let inner = ::std::convert::AsRef::<<$parent as $crate::NewTypeRef>::InnerRef>::as_ref(parent);
+        <$child as $crate::NewTypeRef>::validate(inner)?;
+        Ok(#[allow(unsafe_code)] unsafe { <$child as $crate::TransparentRef>::from_inner_unchecked(inner) })
+    }
+}
+
+impl ::std::convert::TryFrom<<$parent as $crate::NewTypeRef>::Owned> for <$child as $crate::NewTypeRef>::Owned {
+    type Error = <$child as $crate::NewTypeRef>::ValidationError;
+
+    #[inline]
+    fn try_from(parent: <$parent as $crate::NewTypeRef>::Owned) -> Result<Self, Self::Error> {
+        let _: &$child = ::std::convert::TryFrom::try_from(::std::convert::AsRef::<$parent>::as_ref(&parent))?;
+        let inner = $crate::TransparentOwned::into_inner(parent);
+        Ok(#[allow(unsafe_code)] unsafe { $crate::TransparentOwned::from_inner_unchecked(From::from(inner)) })
+    }
+}
+    )+};
+    ($(
+        impl Refines<$parent:ty> for $child:ty;
+    )+) => {
+        compile_error!("upcasts are not validated, so refinements must be declared as `unsafe impl Refines<Parent> for Child;`");
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use Refines;
+
+    fn validate_id(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || value.len() > 64 {
+            return Err("invalid id");
+        }
+        if !value.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+            return Err("invalid character");
+        }
+        Ok(())
+    }
+
+    fn validate_short_id(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || value.len() > 8 {
+            return Err("invalid short id");
+        }
+        if !value.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err("invalid character");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Id(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct IdRef(str) where validate = validate_id, error = &'static str;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct ShortId(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct ShortIdRef(str) where validate = validate_short_id, error = &'static str;
+    }
+
+    new_type_refines! {
+        unsafe impl Refines<IdRef> for ShortIdRef;
+    }
+
+    #[test]
+    fn upcasts_without_validation() {
+        let short = ShortIdRef::try_as_ref("a1b2").unwrap();
+        let id: &IdRef = short.upcast();
+        assert_eq!("a1b2", id);
+        assert_eq!(id, <&IdRef>::from(short));
+        let owned = Id::from(ShortId::from(short));
+        assert_eq!(owned, id);
+    }
+
+    #[test]
+    fn converts_owned_values_without_copying() {
+        let short = ShortId::try_from("a1b2").unwrap();
+        let buffer = AsRef::<str>::as_ref(&*short).as_ptr();
+        let id = Id::from(short);
+        assert_eq!(buffer, AsRef::<str>::as_ref(&*id).as_ptr());
+        let short = <ShortId as TryFrom<Id>>::try_from(id).unwrap();
+        assert_eq!(buffer, AsRef::<str>::as_ref(&*short).as_ptr());
+    }
+
+    #[test]
+    fn downcasts_with_validation() {
+        let id = IdRef::try_as_ref("a1b2").unwrap();
+        assert_eq!(Ok("a1b2"), <&ShortIdRef>::try_from(id).map(AsRef::<str>::as_ref));
+        let id = IdRef::try_as_ref("a1-b2").unwrap();
+        assert_eq!(Err("invalid character"), <&ShortIdRef>::try_from(id).map(|_| ()));
+        let id = Id::try_from("0123456789").unwrap();
+        assert_eq!(Err("invalid short id"), <ShortId as TryFrom<Id>>::try_from(id));
+        let id = Id::try_from("01234567").unwrap();
+        assert_eq!("01234567", <ShortId as TryFrom<Id>>::try_from(id).unwrap());
+    }
+
+    proptest! {
+        #[test]
+        fn refined_values_are_valid_parents(ref s in "[a-z0-9-]{0,10}|.{0,10}") {
+            if let Ok(short) = ShortIdRef::try_as_ref(s) {
+                assert!(IdRef::try_as_ref(s).is_ok());
+                assert_eq!(s.as_str(), AsRef::<str>::as_ref(short.upcast()));
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use unicode_normalization::{self, IsNormalized, UnicodeNormalization};
+use unicode_normalization::char::canonical_combining_class;
+
+use stream::{StreamError, Utf8Chunks};
+use traits::{NewTypeRef, TransparentRef, ValidatorState};
+
+/// The error returned when a value is not in the Unicode normal form
+/// required by `validate_nfc` or `validate_nfkc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NormalizationError {
+    /// The value is not in Normalization Form C
+    NotNfc,
+    /// The value is not in Normalization Form KC
+    NotNfkc,
+}
+
+impl fmt::Display for NormalizationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NormalizationError::NotNfc => f.write_str("value is not in Unicode normalization form C"),
+            NormalizationError::NotNfkc => f.write_str("value is not in Unicode normalization form KC"),
+        }
+    }
+}
+
+impl Error for NormalizationError {}
+
+/// Requires `value` to be in Normalization Form C, in which canonically
+/// equivalent strings, such as a precomposed `é` and an `e` followed by a
+/// combining accent, are spelled the same.
+pub fn validate_nfc(value: &str) -> Result<(), NormalizationError> {
+    if value.is_ascii() || unicode_normalization::is_nfc(value) {
+        Ok(())
+    } else {
+        Err(NormalizationError::NotNfc)
+    }
+}
+
+/// Requires `value` to be in Normalization Form KC, which additionally
+/// spells compatibility characters, such as `ﬁ` or full-width letters, in
+/// their plain forms.
+pub fn validate_nfkc(value: &str) -> Result<(), NormalizationError> {
+    if value.is_ascii() || unicode_normalization::is_nfkc(value) {
+        Ok(())
+    } else {
+        Err(NormalizationError::NotNfkc)
+    }
+}
+
+/// Checks that a value is in a Unicode normal form one chunk at a time, as
+/// `validate_nfc` or `validate_nfkc` would.
+///
+/// The state keeps the text since the last character that no following
+/// character can change when normalizing, which is usually just a few
+/// characters.
+///
+/// ```
+/// use new_type_derive::{NormalFormState, NormalizationError, StreamError, ValidatorState};
+///
+/// let mut state = NormalFormState::nfc();
+/// state.feed(b"cafe").unwrap();
+/// state.feed("\u{301}".as_bytes()).unwrap();
+/// assert_eq!(Err(StreamError::Invalid(NormalizationError::NotNfc)), state.finish());
+/// ```
+#[derive(Clone, Debug)]
+pub struct NormalFormState {
+    validate: fn(&str) -> Result<(), NormalizationError>,
+    quick_check: fn(::std::iter::Once<char>) -> IsNormalized,
+    segment: String,
+    chunks: Utf8Chunks,
+}
+
+impl NormalFormState {
+    /// Checks that values are in Normalization Form C
+    pub fn nfc() -> Self {
+        NormalFormState::new(validate_nfc, unicode_normalization::is_nfc_quick)
+    }
+
+    /// Checks that values are in Normalization Form KC
+    pub fn nfkc() -> Self {
+        NormalFormState::new(validate_nfkc, unicode_normalization::is_nfkc_quick)
+    }
+
+    fn new(
+        validate: fn(&str) -> Result<(), NormalizationError>,
+        quick_check: fn(::std::iter::Once<char>) -> IsNormalized,
+    ) -> Self {
+        NormalFormState { validate, quick_check, segment: String::new(), chunks: Utf8Chunks::default() }
+    }
+
+    fn push(&mut self, piece: &str) -> Result<(), NormalizationError> {
+        for c in piece.chars() {
+            // Normalization never combines or reorders characters across a
+            // starter that is unchanged in the normal form, so the text
+            // before one can be checked on its own.
+            if canonical_combining_class(c) == 0
+                && (self.quick_check)(::std::iter::once(c)) == IsNormalized::Yes
+            {
+                (self.validate)(&self.segment)?;
+                self.segment.clear();
+            }
+            self.segment.push(c);
+        }
+        Ok(())
+    }
+}
+
+impl ValidatorState for NormalFormState {
+    type Error = StreamError<NormalizationError>;
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        let mut chunks = self.chunks;
+        let result = chunks.feed(chunk, |piece| self.push(piece));
+        self.chunks = chunks;
+        result
+    }
+
+    fn finish(self) -> Result<(), Self::Error> {
+        self.chunks.finish()?;
+        (self.validate)(&self.segment).map_err(StreamError::Invalid)
+    }
+}
+
+/// Converts `value` to Normalization Form C, borrowing it if it already is
+pub fn to_nfc(value: &str) -> Cow<'_, str> {
+    if unicode_normalization::is_nfc(value) {
+        Cow::Borrowed(value)
+    } else {
+        Cow::Owned(value.nfc().collect())
+    }
+}
+
+/// Converts `value` to Normalization Form KC, borrowing it if it already is
+pub fn to_nfkc(value: &str) -> Cow<'_, str> {
+    if unicode_normalization::is_nfkc(value) {
+        Cow::Borrowed(value)
+    } else {
+        Cow::Owned(value.nfkc().collect())
+    }
+}
+
+/// Constructors that normalize a value before validating it, available on
+/// every reference type wrapping `str`.
+///
+/// ```
+/// # #[macro_use] extern crate new_type_derive;
+/// # #[cfg(feature = "serde")]
+/// # extern crate serde;
+/// #
+/// use new_type_derive::{validate_nfc, NormalizationError, Normalize};
+///
+/// new_type_pair! {
+///     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct Nickname(String);
+///
+///     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+///     pub struct NicknameRef(str) where validate = validate_nfc, error = NormalizationError;
+/// }
+///
+/// # pub fn main() {
+/// let decomposed = "Rene\u{301}e";
+/// assert_eq!(Err(NormalizationError::NotNfc), Nickname::try_from(decomposed));
+/// assert_eq!("Ren\u{e9}e", NicknameRef::try_from_nfc(decomposed).unwrap());
+/// # }
+/// ```
+pub trait Normalize: TransparentRef<InnerRef = str> {
+    /// Converts `value` to Normalization Form C and then validates it,
+    /// returning an owned value or an error
+    fn try_from_nfc(value: &str) -> Result<Self::Owned, Self::ValidationError> {
+        from_normalized::<Self>(&to_nfc(value))
+    }
+
+    /// Converts `value` to Normalization Form KC and then validates it,
+    /// returning an owned value or an error
+    fn try_from_nfkc(value: &str) -> Result<Self::Owned, Self::ValidationError> {
+        from_normalized::<Self>(&to_nfkc(value))
+    }
+}
+
+impl<R: ?Sized + TransparentRef<InnerRef = str>> Normalize for R {}
+
+fn from_normalized<R>(value: &str) -> Result<R::Owned, R::ValidationError>
+where R: ?Sized + TransparentRef<InnerRef = str> {
+    R::validate(value)?;
+    #[allow(unsafe_code)]
+    let value = unsafe { R::from_inner_unchecked(value) };
+    Ok(NewTypeRef::to_owned(value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_nfc, to_nfkc, validate_nfc, validate_nfkc, NormalFormState, NormalizationError, Normalize};
+    use stream::StreamError;
+    use traits::ValidatorState;
+    use std::borrow::Cow;
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Nfc(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct NfcRef(str) where validate = validate_nfc, error = NormalizationError;
+
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Nfkc(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct NfkcRef(str) where validate = validate_nfkc, error = NormalizationError;
+    }
+
+    #[test]
+    fn validates_normal_forms() {
+        assert_eq!(Ok(()), validate_nfc("caf\u{e9}"));
+        assert_eq!(Err(NormalizationError::NotNfc), validate_nfc("cafe\u{301}"));
+        assert_eq!(Ok(()), validate_nfc("\u{fb01}le"));
+        assert_eq!(Err(NormalizationError::NotNfkc), validate_nfkc("\u{fb01}le"));
+        assert_eq!(Ok(()), validate_nfkc("file"));
+    }
+
+    #[test]
+    fn normalizes_only_when_needed() {
+        assert_eq!(Cow::Borrowed("caf\u{e9}"), to_nfc("caf\u{e9}"));
+        assert_eq!("caf\u{e9}", to_nfc("cafe\u{301}"));
+        assert_eq!("file", to_nfkc("\u{fb01}le"));
+        assert_eq!("file", NfkcRef::try_from_nfkc("\u{fb01}le").unwrap());
+        assert_eq!("caf\u{e9}", NfcRef::try_from_nfc("cafe\u{301}").unwrap());
+    }
+
+    proptest! {
+        #[test]
+        fn nfc_wrapped_equal_or_error_same(ref s in "\\PC{0,8}") {
+            let or = Nfc::try_from(s.to_owned());
+            let rr = NfcRef::try_as_ref(s);
+
+            match (or, rr) {
+                (Ok(o), Ok(r)) => assert_eq!(o, r),
+                (Err(oe), Err(re)) => assert_eq!(oe, re),
+                (Ok(_), Err(e)) => panic!("Owned succeeded while ref failed with: {:?}", e),
+                (Err(e), Ok(_)) => panic!("Ref succeeded while owned failed with: {:?}", e),
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn normalized_values_are_valid(ref s in "\\PC{0,8}") {
+            let nfc = NfcRef::try_from_nfc(s).expect("normalized values to be valid");
+            assert_eq!(to_nfc(s), AsRef::<str>::as_ref(&*nfc));
+            let nfkc = NfkcRef::try_from_nfkc(s).expect("normalized values to be valid");
+            assert_eq!(Ok(()), validate_nfc(AsRef::<str>::as_ref(&*nfkc)));
+        }
+    }
+
+    fn stream(mut state: NormalFormState, value: &str, size: usize) -> Result<(), StreamError<NormalizationError>> {
+        for chunk in value.as_bytes().chunks(size) {
+            state.feed(chunk)?;
+        }
+        state.finish()
+    }
+
+    #[test]
+    fn streams_check_characters_split_across_chunks() {
+        assert_eq!(Ok(()), stream(NormalFormState::nfc(), "caf\u{e9}", 1));
+        assert_eq!(Err(StreamError::Invalid(NormalizationError::NotNfc)), stream(NormalFormState::nfc(), "cafe\u{301}", 1));
+        assert_eq!(Ok(()), stream(NormalFormState::nfc(), "\u{fb01}le", 2));
+        assert_eq!(Err(StreamError::Invalid(NormalizationError::NotNfkc)), stream(NormalFormState::nfkc(), "\u{fb01}le", 2));
+        let mut state = NormalFormState::nfc();
+        assert_eq!(Ok(()), state.feed(&"\u{e9}".as_bytes()[..1]));
+        assert_eq!(Err(StreamError::InvalidUtf8), state.finish());
+    }
+
+    proptest! {
+        #[test]
+        fn streams_agree_with_validate(ref s in "[ae\u{e9}\u{301}\u{327}\u{1100}\u{1161}\u{11a8}\u{ac00}\u{fb01}\u{ff41}]{0,8}", size in 1usize..6) {
+            assert_eq!(validate_nfc(s).map_err(StreamError::Invalid), stream(NormalFormState::nfc(), s, size));
+            assert_eq!(validate_nfkc(s).map_err(StreamError::Invalid), stream(NormalFormState::nfkc(), s, size));
+        }
+    }
+}
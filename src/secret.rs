@@ -0,0 +1,118 @@
+use std::hint;
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+
+/// Compares two strings in time that depends only on their lengths, not on
+/// where they first differ.
+///
+/// This is used for every equality comparison of pairs declared with the
+/// `secret` option of `new_type_pair!`.
+pub fn constant_time_eq(lhs: &str, rhs: &str) -> bool {
+    if lhs.len() != rhs.len() {
+        return false;
+    }
+    let diff = lhs
+        .bytes()
+        .zip(rhs.bytes())
+        .fold(0u8, |diff, (l, r)| diff | (l ^ r));
+    hint::black_box(diff) == 0
+}
+
+/// Overwrites every byte of `value` with zero in a way that the compiler
+/// will not optimize away.
+///
+/// This is used to clear the owned value of pairs declared with the `secret`
+/// option of `new_type_pair!` when it is dropped.
+pub fn zeroize<T: ?Sized + Zeroize>(value: &mut T) {
+    value.zeroize()
+}
+
+/// Values that can clear the memory holding them, which the owned type
+/// wrapped by a pair declared with the `secret` option must implement.
+///
+/// `String` and `Vec<u8>` clear their whole buffer, including any spare
+/// capacity left over from longer values they held before.
+pub trait Zeroize {
+    /// Overwrites the memory holding the value with zeros.
+    fn zeroize(&mut self);
+}
+
+impl Zeroize for str {
+    fn zeroize(&mut self) {
+        // Zero bytes are valid UTF-8, so `self` remains a valid `str`.
+        #[allow(unsafe_code)]
+        let bytes = unsafe { self.as_bytes_mut() };
+        zero(bytes.as_mut_ptr(), bytes.len());
+    }
+}
+
+impl Zeroize for String {
+    fn zeroize(&mut self) {
+        // Zero bytes are valid UTF-8, so `self` remains a valid `String`.
+        #[allow(unsafe_code)]
+        let bytes = unsafe { self.as_mut_vec() };
+        Zeroize::zeroize(bytes)
+    }
+}
+
+impl Zeroize for Vec<u8> {
+    fn zeroize(&mut self) {
+        // The spare capacity is allocated, so it may be written through the
+        // pointer even though it is not part of the vector.
+        zero(self.as_mut_ptr(), self.capacity());
+    }
+}
+
+impl Zeroize for Box<str> {
+    fn zeroize(&mut self) {
+        Zeroize::zeroize(&mut **self)
+    }
+}
+
+fn zero(bytes: *mut u8, len: usize) {
+    for i in 0..len {
+        #[allow(unsafe_code)]
+        unsafe { ptr::write_volatile(bytes.add(i), 0) };
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{constant_time_eq, zeroize};
+
+    #[test]
+    fn compares_like_eq() {
+        assert!(constant_time_eq("", ""));
+        assert!(constant_time_eq("hunter2", "hunter2"));
+        assert!(!constant_time_eq("hunter2", "hunter3"));
+        assert!(!constant_time_eq("hunter2", "hunter22"));
+    }
+
+    #[test]
+    fn zeroizes_every_byte() {
+        let mut value = String::from("Ĉu ĝustas?");
+        zeroize(&mut value);
+        assert!(value.bytes().all(|b| b == 0));
+        assert_eq!("Ĉu ĝustas?".len(), value.len());
+    }
+
+    #[test]
+    fn zeroizes_spare_capacity() {
+        let mut value = String::from("hunter2 hunter2");
+        value.truncate(3);
+        let capacity = value.capacity();
+        zeroize(&mut value);
+        #[allow(unsafe_code)]
+        let buffer = unsafe { ::std::slice::from_raw_parts(value.as_ptr(), capacity) };
+        assert!(buffer.iter().all(|&b| b == 0));
+        assert_eq!(3, value.len());
+    }
+
+    proptest! {
+        #[test]
+        fn constant_time_eq_agrees_with_eq(ref a in ".{0,4}", ref b in ".{0,4}") {
+            assert_eq!(a == b, constant_time_eq(a, b));
+        }
+    }
+}
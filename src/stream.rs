@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fmt;
+use std::str;
+
+/// The error returned by a `ValidatorState` when the bytes fed to it are not
+/// valid UTF-8 or the value they spell is not valid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StreamError<E> {
+    /// The bytes are not valid UTF-8, or end in the middle of a character
+    InvalidUtf8,
+    /// The value is not valid
+    Invalid(E),
+}
+
+impl<E: fmt::Display> fmt::Display for StreamError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StreamError::InvalidUtf8 => f.write_str("value is not valid UTF-8"),
+            StreamError::Invalid(ref error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+impl<E: Error> Error for StreamError<E> {}
+
+/// Splits chunks of bytes into strings for incremental validators, carrying
+/// a character split across two chunks over to the next one.
+///
+/// ```
+/// use new_type_derive::{StreamError, Utf8Chunks};
+///
+/// let mut chunks = Utf8Chunks::default();
+/// let mut pieces = Vec::new();
+/// for chunk in "ĝuste".as_bytes().chunks(1) {
+///     chunks.feed(chunk, |piece| Ok::<_, ()>(pieces.push(piece.to_owned()))).unwrap();
+/// }
+/// assert_eq!(Ok(()), chunks.finish::<()>());
+/// assert_eq!("ĝuste", pieces.concat());
+/// assert_eq!(Err(StreamError::InvalidUtf8), chunks.feed(b"\xff", |_| Ok::<_, ()>(())));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Utf8Chunks {
+    pending: [u8; 4],
+    len: usize,
+}
+
+impl Utf8Chunks {
+    /// Passes the complete characters of `chunk`, along with any character
+    /// completed by its first bytes, to `f`, and keeps a character split at
+    /// the end of `chunk` for the next call
+    pub fn feed<E, F>(&mut self, mut chunk: &[u8], mut f: F) -> Result<(), StreamError<E>>
+    where F: FnMut(&str) -> Result<(), E> {
+        if self.len > 0 {
+            let width = match self.pending[0] {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            let take = chunk.len().min(width - self.len);
+            self.pending[self.len..self.len + take].copy_from_slice(&chunk[..take]);
+            self.len += take;
+            chunk = &chunk[take..];
+            match str::from_utf8(&self.pending[..self.len]) {
+                Ok(c) => {
+                    self.len = 0;
+                    f(c).map_err(StreamError::Invalid)?;
+                }
+                Err(ref e) if e.error_len().is_none() => return Ok(()),
+                Err(_) => return Err(StreamError::InvalidUtf8),
+            }
+        }
+        let valid = match str::from_utf8(chunk) {
+            Ok(_) => chunk.len(),
+            Err(ref e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(StreamError::InvalidUtf8),
+        };
+        let (complete, rest) = chunk.split_at(valid);
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.len = rest.len();
+        match str::from_utf8(complete) {
+            Ok(complete) if !complete.is_empty() => f(complete).map_err(StreamError::Invalid),
+            Ok(_) => Ok(()),
+            Err(_) => Err(StreamError::InvalidUtf8),
+        }
+    }
+
+    /// Checks that the bytes fed so far did not end in the middle of a
+    /// character
+    pub fn finish<E>(&self) -> Result<(), StreamError<E>> {
+        if self.len > 0 {
+            Err(StreamError::InvalidUtf8)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StreamError, Utf8Chunks};
+    use length::{LengthError, LengthLimit, LengthState};
+    use traits::StreamValidate;
+
+    const NAME_LIMIT: LengthLimit = LengthLimit::chars(4);
+
+    fn validate_name(value: &str) -> Result<(), LengthError> {
+        NAME_LIMIT.validate(value)
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Name(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct NameRef(str) where validate = validate_name, error = LengthError;
+    }
+
+    impl StreamValidate for NameRef {
+        type State = LengthState;
+
+        fn validator_state() -> LengthState {
+            NAME_LIMIT.stream()
+        }
+    }
+
+    fn pieces(value: &[u8], size: usize) -> Result<String, StreamError<()>> {
+        let mut chunks = Utf8Chunks::default();
+        let mut joined = String::new();
+        for chunk in value.chunks(size) {
+            chunks.feed(chunk, |piece| {
+                assert!(!piece.is_empty());
+                joined.push_str(piece);
+                Ok(())
+            })?;
+        }
+        chunks.finish()?;
+        Ok(joined)
+    }
+
+    #[test]
+    fn rejects_invalid_utf8() {
+        assert_eq!(Err(StreamError::InvalidUtf8), pieces(b"ab\xffcd", 2));
+        assert_eq!(Err(StreamError::InvalidUtf8), pieces(b"\xc4a", 1));
+        assert_eq!(Err(StreamError::InvalidUtf8), pieces("ĝ".as_bytes().split_at(1).0, 1));
+        assert_eq!(Err(StreamError::InvalidUtf8), pieces(b"\xf0\x9f\x98", 4));
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let mut chunks = Utf8Chunks::default();
+        assert_eq!(Err(StreamError::Invalid("no")), chunks.feed(b"ab", |_| Err("no")));
+    }
+
+    #[test]
+    fn validates_reference_types_in_chunks() {
+        let value = "ĝuste".as_bytes();
+        assert!(NameRef::validate_chunks(vec![&value[..1], &value[1..4]]).is_ok());
+        assert!(NameRef::validate_chunks(value.chunks(1)).is_err());
+        assert_eq!(Err(StreamError::InvalidUtf8), NameRef::validate_chunks(vec![&value[..1]]));
+    }
+
+    proptest! {
+        #[test]
+        fn joins_split_characters(ref s in "\\PC{0,16}", size in 1usize..6) {
+            assert_eq!(Ok(s.to_owned()), pieces(s.as_bytes(), size));
+        }
+    }
+}
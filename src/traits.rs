@@ -1,26 +1,223 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use mask::Mask;
+use stream::StreamError;
+
 /// A trait that provides necessary operations for creating a new type with
 /// reference type that can round-trip between the two types and the original
 /// wrapped value.
 pub trait NewTypeRef {
     /// The owned type, must be able to take `Self` as a reference.
+    ///
+    /// Types declared with `new_type_owned!` use `Self`, and types declared
+    /// with `new_type_ref!` will usually use `Box<Self>`.
     type Owned: AsRef<Self>;
     /// The type of the inner value for the reference type, e.g. `str` or
     /// `[u8]`.
     ///
-    /// Currently only `str` is supported.
+    /// Currently only `str` and reference types layered on it are
+    /// supported.
     type InnerRef: ?Sized;
     /// The error type that is returned in the event validation fails.
     type ValidationError;
 
+    /// How values are masked when displayed through `masked`, which hides
+    /// every character unless overridden.
+    const MASK: Mask = Mask::All;
+
     #[allow(unused_variables)]
     /// Validate the value before allowing it to be wrapped in the new type.
     fn validate(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
         Ok(())
     }
 
+    #[allow(unused_variables)]
+    /// Rejects a value that cannot be the start of any valid value, so that
+    /// builders can stop early. This accepts every value unless overridden,
+    /// and must accept every prefix of a valid value.
+    fn validate_prefix(value: &Self::InnerRef) -> Result<(), Self::ValidationError> {
+        Ok(())
+    }
+
+    /// Finds the next candidate value in `text` that starts at or after the
+    /// byte index `from`, returning its byte range, for `find_iter` to
+    /// validate. Searching continues from the end of the range, which must
+    /// lie past `from`.
+    ///
+    /// Unless overridden, candidates are runs of characters other than
+    /// whitespace, without any leading or trailing ASCII punctuation.
+    fn next_token(text: &str, from: usize) -> Option<Range<usize>> {
+        let rest = text.get(from..)?;
+        let start = from + rest.find(|c: char| !c.is_whitespace() && !c.is_ascii_punctuation())?;
+        let word = text[start..].split(char::is_whitespace).next().unwrap_or("");
+        let word = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
+        Some(start..start + word.len())
+    }
+
     /// Convert the reference into an owned value.
     ///
     /// The implementation of this must not fail in order for valid values to
     /// round-trip between the owned and reference wrappers.
     fn to_owned(&self) -> Self::Owned;
 }
+
+/// A reference new type that shares the layout of the value it wraps, so
+/// that a value can be viewed as the new type without copying it.
+///
+/// This is implemented for every reference type declared by the macros in
+/// this crate, and lets generic code such as `Interner` hand out typed
+/// references to values it has already validated.
+///
+/// # Safety
+///
+/// `Self` must have the same layout as `Self::InnerRef`, such as a
+/// `#[repr(transparent)]` struct wrapping it.
+#[allow(unsafe_code)]
+pub unsafe trait TransparentRef: NewTypeRef {
+    /// Views `value` as the new type without validating it.
+    ///
+    /// # Safety
+    ///
+    /// `value` must have been accepted by `NewTypeRef::validate`.
+    unsafe fn from_inner_unchecked(value: &Self::InnerRef) -> &Self;
+}
+
+/// An owned new type that can be unwrapped into the value it wraps and
+/// wrapped again without copying it.
+///
+/// This is implemented for the owned types declared by `new_type_pair!`,
+/// except secret ones, which are cleared when dropped, and for `Box<Self>` of
+/// the reference types declared by `new_type_ref!`.
+pub trait TransparentOwned: Sized {
+    /// The type of the wrapped value.
+    type Inner;
+
+    /// Unwraps the value.
+    fn into_inner(self) -> Self::Inner;
+
+    /// Wraps `value` without validating it.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be accepted by `NewTypeRef::validate` of the reference
+    /// type.
+    #[allow(unsafe_code)]
+    unsafe fn from_inner_unchecked(value: Self::Inner) -> Self;
+}
+
+/// A reference type whose validator accepts every substring of a valid
+/// value, such as one that only checks which characters a value contains.
+///
+/// This is implemented by `new_type_pair!` for pairs declared with `unsafe
+/// substring_closed`, whose slicing operations such as `get` and `trim` then
+/// skip validating the slices, except in builds with debug assertions.
+///
+/// # Safety
+///
+/// `NewTypeRef::validate` must accept every substring of a value that it
+/// accepts, including the empty string.
+#[allow(unsafe_code)]
+pub unsafe trait SubstringClosed: NewTypeRef {}
+
+/// A reference type whose valid values are all valid values of `Parent`, so
+/// that it converts to `Parent` without validating the value again.
+///
+/// This is implemented by `new_type_refines!`, along with conversions
+/// between the owned and reference types of both.
+///
+/// # Safety
+///
+/// `NewTypeRef::validate` of `Parent` must accept every value that
+/// `NewTypeRef::validate` of this type accepts, since `upcast` and the
+/// conversions skip validating the value as `Parent`, except in builds with
+/// debug assertions.
+#[allow(unsafe_code)]
+pub unsafe trait Refines<Parent: ?Sized>: NewTypeRef {
+    /// Views the value as the parent type.
+    fn upcast(&self) -> &Parent;
+}
+
+/// Reference types that can be created from a `str`, either because they
+/// wrap it directly or because they are layered on other reference types
+/// that do.
+///
+/// This is implemented for every reference type declared by
+/// `new_type_pair!`. Layered types validate the value as each type in the
+/// stack in turn, starting from the one wrapping `str`.
+pub trait TryFromStr {
+    /// The error returned when any of the validations fails.
+    type Error;
+
+    /// Validates `value` as every type in the stack, returning a typed
+    /// reference to it or the first error.
+    fn try_from_str(value: &str) -> Result<&Self, Self::Error>;
+}
+
+/// A validator that checks a value one chunk of UTF-8 bytes at a time, such
+/// as while it is read from a network buffer, rather than all at once.
+///
+/// Chunks may split characters, which `Utf8Chunks` puts back together for
+/// implementations. Implementations should reject values as soon as they
+/// can tell that no continuation would make them valid.
+pub trait ValidatorState {
+    /// The error returned when the bytes are not a valid value.
+    type Error;
+
+    /// Checks the next chunk of the value.
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), Self::Error>;
+
+    /// Checks that the chunks fed so far make up a complete, valid value.
+    fn finish(self) -> Result<(), Self::Error>;
+}
+
+/// Reference types whose values can be validated incrementally, which must
+/// accept exactly the values accepted by `NewTypeRef::validate`.
+///
+/// The crate provides states for its own validators, such as
+/// `LengthLimit::stream`, which implementations can return from
+/// `validator_state`.
+pub trait StreamValidate: NewTypeRef {
+    /// The incremental validator.
+    type State: ValidatorState<Error = StreamError<Self::ValidationError>>;
+
+    /// Creates a validator for a new value.
+    fn validator_state() -> Self::State;
+
+    /// Validates a value split into `chunks`.
+    fn validate_chunks<'a, I>(chunks: I) -> Result<(), StreamError<Self::ValidationError>>
+    where I: IntoIterator<Item = &'a [u8]> {
+        let mut state = Self::validator_state();
+        for chunk in chunks {
+            state.feed(chunk)?;
+        }
+        state.finish()
+    }
+}
+
+/// A way of comparing and hashing the strings wrapped by a new type, which
+/// replaces the usual byte-wise comparisons of pairs declared with the
+/// `compare` option of `new_type_pair!`.
+///
+/// Implementations must be consistent with each other: values that are
+/// equal must hash alike and compare as `Ordering::Equal`, and must have the
+/// same canonical form.
+pub trait Comparison {
+    /// Compares two values for equality.
+    fn eq(lhs: &str, rhs: &str) -> bool {
+        Self::cmp(lhs, rhs) == Ordering::Equal
+    }
+
+    /// Orders two values.
+    fn cmp(lhs: &str, rhs: &str) -> Ordering;
+
+    /// Feeds a value into `state`.
+    fn hash<H: Hasher>(value: &str, state: &mut H) {
+        Hash::hash(&*Self::canonical(value), state)
+    }
+
+    /// Returns the form shared by every value that is equal to `value`.
+    fn canonical(value: &str) -> Cow<'_, str>;
+}
@@ -0,0 +1,255 @@
+//! Deserializes owned types of pairs without validating them, for use with
+//! `#[serde(with = "new_type_derive::trusted_unchecked")]` on fields holding
+//! values that were validated before they were stored, such as rows read back
+//! from a database or a cache this program wrote.
+//!
+//! Values that were never validated break the invariants of the new type, so
+//! this must only be used for data from trusted sources. Builds with debug
+//! assertions still validate every value, returning the same errors as the
+//! usual `Deserialize` implementations.
+//!
+//! ```
+//! # #[macro_use] extern crate new_type_derive;
+//! # extern crate serde;
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate bincode;
+//! #
+//! fn validate_user_id(value: &str) -> Result<(), &'static str> {
+//!     if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+//!         return Err("user ids are numeric");
+//!     }
+//!     Ok(())
+//! }
+//!
+//! new_type_pair! {
+//!     #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+//!     pub struct UserId(String);
+//!
+//!     #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+//!     pub struct UserIdRef(str) where validate = validate_user_id, error = &'static str;
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct CachedUser<'a> {
+//!     #[serde(with = "new_type_derive::trusted_unchecked")]
+//!     id: UserId,
+//!     #[serde(borrow, with = "new_type_derive::trusted_unchecked::borrowed")]
+//!     manager: &'a UserIdRef,
+//! }
+//!
+//! # pub fn main() {
+//! let manager = UserIdRef::try_as_ref("7").unwrap();
+//! let user = CachedUser { id: UserId::try_from("42").unwrap(), manager };
+//! let cached = bincode::serialize(&user).unwrap();
+//! let user: CachedUser = bincode::deserialize(&cached).unwrap();
+//! assert_eq!("42", user.id);
+//! assert_eq!("7", user.manager);
+//! # }
+//! ```
+
+use serde::{de, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use traits::{NewTypeRef, TransparentOwned, TransparentRef};
+
+/// Serializes `value` as usual
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    value.serialize(serializer)
+}
+
+/// Deserializes an owned type without validating it, except in builds with
+/// debug assertions. Strings handed over by the deserializer are wrapped
+/// without copying them.
+///
+/// Secret pairs do not implement `TransparentOwned`, so they cannot be
+/// deserialized through this module.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Deref + TransparentOwned,
+    T::Target: TransparentRef<InnerRef = str, Owned = T>,
+    T::Inner: From<String>,
+    <T::Target as NewTypeRef>::ValidationError: fmt::Display,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_string(OwnedVisitor(PhantomData))
+}
+
+/// Deserializes references to reference types, borrowed from the input,
+/// without validating them, except in builds with debug assertions.
+///
+/// Fields using this module must also be marked `#[serde(borrow)]`. Values
+/// that cannot be borrowed from the input, such as strings containing escape
+/// sequences in JSON or any value read from an `io::Read`, fail with an
+/// "invalid type" error rather than being copied.
+pub mod borrowed {
+    use serde::{Deserializer, Serialize, Serializer};
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    use super::BorrowedVisitor;
+    use traits::TransparentRef;
+
+    /// Serializes `value` as usual
+    pub fn serialize<R, S>(value: &&R, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: ?Sized + Serialize,
+        S: Serializer,
+    {
+        (**value).serialize(serializer)
+    }
+
+    /// Deserializes a reference without validating it, except in builds
+    /// with debug assertions
+    pub fn deserialize<'de: 'a, 'a, R, D>(deserializer: D) -> Result<&'a R, D::Error>
+    where
+        R: ?Sized + TransparentRef<InnerRef = str>,
+        R::ValidationError: fmt::Display,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(BorrowedVisitor(PhantomData))
+    }
+}
+
+fn trusted<R, E>(value: &str) -> Result<&R, E>
+where
+    R: ?Sized + TransparentRef<InnerRef = str>,
+    R::ValidationError: fmt::Display,
+    E: de::Error,
+{
+    if cfg!(debug_assertions) {
+        R::validate(value).map_err(E::custom)?;
+    }
+    // The caller vouches that the value was validated before it was stored.
+    Ok(#[allow(unsafe_code)] unsafe { R::from_inner_unchecked(value) })
+}
+
+struct OwnedVisitor<T>(PhantomData<T>);
+
+impl<'de, T> de::Visitor<'de> for OwnedVisitor<T>
+where
+    T: Deref + TransparentOwned,
+    T::Target: TransparentRef<InnerRef = str, Owned = T>,
+    T::Inner: From<String>,
+    <T::Target as NewTypeRef>::ValidationError: fmt::Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+        trusted::<T::Target, E>(value).map(NewTypeRef::to_owned)
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<T, E> {
+        trusted::<T::Target, E>(&value)?;
+        // The caller vouches that the value was validated before it was stored.
+        Ok(#[allow(unsafe_code)] unsafe { T::from_inner_unchecked(From::from(value)) })
+    }
+}
+
+struct BorrowedVisitor<'a, R: ?Sized + 'a>(PhantomData<&'a R>);
+
+impl<'de: 'a, 'a, R> de::Visitor<'de> for BorrowedVisitor<'a, R>
+where
+    R: ?Sized + TransparentRef<InnerRef = str>,
+    R::ValidationError: fmt::Display,
+{
+    type Value = &'a R;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a borrowed string")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<&'a R, E> {
+        trusted(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bincode;
+
+    fn validate_digits(value: &str) -> Result<(), &'static str> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err("invalid digits");
+        }
+        Ok(())
+    }
+
+    new_type_pair! {
+        #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Digits(String);
+
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct DigitsRef(str) where validate = validate_digits, error = &'static str;
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Row<'a> {
+        #[serde(with = "::trusted_unchecked")]
+        owned: Digits,
+        #[serde(borrow, with = "::trusted_unchecked::borrowed")]
+        borrowed: &'a DigitsRef,
+    }
+
+    #[derive(Serialize)]
+    struct RawRow<'a> {
+        owned: &'a str,
+        borrowed: &'a str,
+    }
+
+    #[test]
+    fn roundtrips_trusted_values() {
+        let row = Row { owned: Digits::try_from("42").unwrap(), borrowed: DigitsRef::try_as_ref("7").unwrap() };
+        let serialized = bincode::serialize(&row).unwrap();
+        assert_eq!(bincode::serialize(&RawRow { owned: "42", borrowed: "7" }).unwrap(), serialized);
+        assert_eq!(row, bincode::deserialize(&serialized).unwrap());
+    }
+
+    #[test]
+    fn wraps_owned_strings_without_copying() {
+        use serde::de::IntoDeserializer;
+        use serde::de::value::Error;
+
+        let value = String::from("42");
+        let buffer = value.as_ptr();
+        let owned: Digits = super::deserialize(IntoDeserializer::<Error>::into_deserializer(value)).unwrap();
+        assert_eq!(buffer, AsRef::<str>::as_ref(&*owned).as_ptr());
+    }
+
+    #[test]
+    fn borrowed_values_must_be_borrowable() {
+        use serde::de::IntoDeserializer;
+        use serde::de::value::Error;
+
+        let deserializer = IntoDeserializer::<Error>::into_deserializer("7");
+        let error = super::borrowed::deserialize::<DigitsRef, _>(deserializer).unwrap_err();
+        assert!(error.to_string().starts_with("invalid type"), "{}", error);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn validates_with_debug_assertions() {
+        let serialized = bincode::serialize(&RawRow { owned: "42", borrowed: "x" }).unwrap();
+        assert!(bincode::deserialize::<Row>(&serialized).is_err());
+        let serialized = bincode::serialize(&RawRow { owned: "", borrowed: "7" }).unwrap();
+        assert!(bincode::deserialize::<Row>(&serialized).is_err());
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn skips_validation_without_debug_assertions() {
+        let serialized = bincode::serialize(&RawRow { owned: "", borrowed: "x" }).unwrap();
+        let row: Row = bincode::deserialize(&serialized).unwrap();
+        assert_eq!("", row.owned);
+        assert_eq!("x", row.borrowed);
+    }
+}
@@ -0,0 +1,158 @@
+#![cfg(feature = "serde")]
+
+#[macro_use] extern crate new_type_derive;
+#[macro_use]
+extern crate serde;
+extern crate bincode;
+
+use serde::de::value::{Error, StringDeserializer};
+use serde::de::{Deserialize, Deserializer, IntoDeserializer, Visitor};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts the allocations made by the current thread, along with the size of
+/// the largest one.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| {
+            let (count, largest) = allocations.get();
+            allocations.set((count + 1, largest.max(layout.size())));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<T, F: FnOnce() -> T>(f: F) -> (T, usize, usize) {
+    ALLOCATIONS.with(|allocations| allocations.set((0, 0)));
+    let result = f();
+    let (count, largest) = ALLOCATIONS.with(Cell::get);
+    (result, count, largest)
+}
+
+/// Hands bytes to visitors, either lent or owned, as formats without a
+/// string type do.
+enum Bytes<'a> {
+    Lent(&'a [u8]),
+    Owned(Vec<u8>),
+}
+
+impl<'de, 'a> Deserializer<'de> for Bytes<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Bytes::Lent(bytes) => visitor.visit_bytes(bytes),
+            Bytes::Owned(bytes) => visitor.visit_byte_buf(bytes),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit unit_struct
+        newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+fn owned(value: &str) -> StringDeserializer<Error> {
+    String::from(value).into_deserializer()
+}
+
+fn validate_tag(value: &str) -> Result<(), &'static str> {
+    if value.len() > 16 {
+        return Err("tags have at most 16 bytes");
+    }
+    Ok(())
+}
+
+new_type_pair! {
+    #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct Tag(String);
+
+    #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct TagRef(str) where validate = validate_tag, error = &'static str;
+}
+
+new_type_owned! {
+    #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct Label(String);
+}
+
+impl new_type_derive::NewTypeRef for Label {
+    type Owned = Label;
+    type InnerRef = str;
+    type ValidationError = &'static str;
+
+    fn validate(value: &str) -> Result<(), &'static str> {
+        validate_tag(value)
+    }
+
+    fn to_owned(&self) -> Label {
+        self.clone()
+    }
+}
+
+#[test]
+fn rejects_invalid_values_before_allocating_them() {
+    let oversized = bincode::serialize(&"x".repeat(1 << 20)).unwrap();
+    let (result, _, largest) = allocations(|| bincode::deserialize::<Tag>(&oversized));
+    assert!(result.is_err());
+    assert!(largest < 1024, "allocated {} bytes for an invalid value", largest);
+}
+
+#[test]
+fn allocates_valid_values_once() {
+    let serialized = bincode::serialize("release").unwrap();
+    let (tag, count, largest) = allocations(|| bincode::deserialize::<Tag>(&serialized).unwrap());
+    assert_eq!("release", tag);
+    assert_eq!((1, 7), (count, largest));
+}
+
+#[test]
+fn rejects_invalid_owned_only_values_before_allocating_them() {
+    let oversized = bincode::serialize(&"x".repeat(1 << 20)).unwrap();
+    let (result, _, largest) = allocations(|| bincode::deserialize::<Label>(&oversized));
+    assert!(result.is_err());
+    assert!(largest < 1024, "allocated {} bytes for an invalid value", largest);
+    let serialized = bincode::serialize("release").unwrap();
+    let (label, count, _) = allocations(|| bincode::deserialize::<Label>(&serialized).unwrap());
+    assert_eq!("release", label);
+    assert_eq!(1, count);
+}
+
+#[test]
+fn moves_owned_strings_into_values() {
+    let deserializer = owned("release");
+    let (tag, count, _) = allocations(|| Tag::deserialize(deserializer).unwrap());
+    assert_eq!("release", tag);
+    assert_eq!(0, count);
+    assert!(Tag::deserialize(owned(&"x".repeat(17))).is_err());
+    let deserializer = owned("release");
+    let (label, count, _) = allocations(|| Label::deserialize(deserializer).unwrap());
+    assert_eq!("release", label);
+    assert_eq!(0, count);
+}
+
+#[test]
+fn decodes_bytes_as_utf8() {
+    assert_eq!("release", Tag::deserialize(Bytes::Lent(b"release")).unwrap());
+    let bytes = Bytes::Owned(b"release".to_vec());
+    let (tag, count, _) = allocations(|| Tag::deserialize(bytes).unwrap());
+    assert_eq!("release", tag);
+    assert_eq!(0, count);
+    assert!(Tag::deserialize(Bytes::Lent(b"\xffrelease")).is_err());
+    assert!(Tag::deserialize(Bytes::Owned(b"\xffrelease".to_vec())).is_err());
+    assert_eq!("release", Label::deserialize(Bytes::Owned(b"release".to_vec())).unwrap());
+    assert!(Label::deserialize(Bytes::Lent(b"\xffrelease")).is_err());
+}
@@ -0,0 +1,93 @@
+#[macro_use] extern crate new_type_derive;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts the bytes that were not zero in the blocks freed by the current
+/// thread, so that tests can observe buffers after they are dropped.
+struct InspectingAllocator;
+
+thread_local! {
+    static DIRTY_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for InspectingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let block = std::slice::from_raw_parts(ptr, layout.size());
+        let dirty = block.iter().filter(|&&b| b != 0).count();
+        let _ = DIRTY_BYTES.try_with(|bytes| bytes.set(bytes.get() + dirty));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: InspectingAllocator = InspectingAllocator;
+
+fn dirty_bytes_freed<F: FnOnce()>(f: F) -> usize {
+    DIRTY_BYTES.with(|bytes| bytes.set(0));
+    f();
+    DIRTY_BYTES.with(Cell::get)
+}
+
+fn validate_password(value: &str) -> Result<(), &'static str> {
+    if value.is_empty() {
+        return Err("passwords cannot be empty");
+    }
+    Ok(())
+}
+
+new_type_pair! {
+    pub struct Password(String);
+
+    pub struct PasswordRef(str) where validate = validate_password, error = &'static str, secret;
+
+    pub struct Pin(Box<str>);
+
+    pub struct PinRef(str) where validate = validate_password, error = &'static str, secret;
+}
+
+#[test]
+fn clears_the_whole_buffer_when_dropped() {
+    let mut value = String::with_capacity(64);
+    value.push_str("correct horse battery staple");
+    value.truncate(7);
+    let password = Password::try_from(value).unwrap();
+    assert_eq!(0, dirty_bytes_freed(|| drop(password)));
+}
+
+#[test]
+fn clears_boxed_values_when_dropped() {
+    let pin = Pin::try_from("0451").unwrap();
+    assert_eq!(0, dirty_bytes_freed(|| drop(pin)));
+}
+
+#[test]
+fn plain_strings_are_not_cleared() {
+    let value = String::from("correct horse battery staple");
+    assert_ne!(0, dirty_bytes_freed(|| drop(value)));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn clears_rejected_strings_handed_over_when_deserializing() {
+    use serde::de::value::Error;
+    use serde::de::{Deserialize, IntoDeserializer};
+
+    let mut value = String::from("hunter2");
+    value.clear();
+    let deserializer = IntoDeserializer::<Error>::into_deserializer(value);
+    let mut result = None;
+    assert_eq!(0, dirty_bytes_freed(|| result = Some(Password::deserialize(deserializer))));
+    assert!(result.unwrap().is_err());
+
+    let deserializer = IntoDeserializer::<Error>::into_deserializer(String::from("hunter2"));
+    let mut password = None;
+    assert_eq!(0, dirty_bytes_freed(|| password = Some(Password::deserialize(deserializer).unwrap())));
+    assert_eq!(0, dirty_bytes_freed(|| drop(password)));
+}